@@ -0,0 +1,57 @@
+// cargo bench --bench bigint
+//
+// times BigUint multiplication and division at sizes around the switch to karatsuba
+// and to newton's reciprocal, and the batch gcd on corpora of 1024 bit moduli that
+// lean on both at the top of the trees
+
+use prime_factorization::{batch::batch_gcd, bigint::BigUint};
+use std::{hint::black_box, time::Instant};
+
+fn bench<T>(name: &str, runs: u32, f: impl Fn() -> T) {
+    // one warm-up run for the thread pool and the caches
+    black_box(f());
+    let started = Instant::now();
+    for _ in 0..runs {
+        black_box(f());
+    }
+    println!("{name:<48} {:>10.2?}", started.elapsed() / runs);
+}
+
+// pseudo random numbers of `limbs` full limbs, the same every run
+fn random(state: &mut u64, limbs: usize) -> BigUint {
+    let hex: String = (0..limbs)
+        .map(|_| {
+            // xorshift
+            *state ^= *state << 13;
+            *state ^= *state >> 7;
+            *state ^= *state << 17;
+            format!("{:016x}", *state | 1 << 63)
+        })
+        .collect();
+    BigUint::from_str_radix(&hex, 16).unwrap()
+}
+
+fn main() {
+    let mut state = 0x9e37_79b9_7f4a_7c15;
+    for limbs in [16, 64, 256, 1024, 4096, 16384] {
+        let (a, b) = (random(&mut state, limbs), random(&mut state, limbs));
+        let runs = (1 << 20) / (limbs * limbs) as u32 + 1;
+        bench(&format!("multiply, {limbs} by {limbs} limbs"), runs, || {
+            black_box(&a) * black_box(&b)
+        });
+        let n = &a * &b;
+        bench(
+            &format!("divide, {} by {limbs} limbs", 2 * limbs),
+            runs,
+            || black_box(&n).div_rem(black_box(&b)),
+        );
+    }
+    for count in [1_000, 4_000, 16_000] {
+        let moduli: Vec<BigUint> = (0..count).map(|_| random(&mut state, 16)).collect();
+        bench(
+            &format!("batch_gcd, {count} moduli of 1024 bits"),
+            1,
+            || batch_gcd(&moduli),
+        );
+    }
+}
@@ -0,0 +1,114 @@
+// cargo bench --bench factoring
+//
+// times the methods find_divisor picks from on semiprimes from factorize, of a
+// given bit length: products of consecutive primes, of primes of about the same
+// size and of primes up to eight bits apart
+//
+// on one core, squfof against rho for balanced and spread samples
+//
+//   40 bits    1.02ms /  726µs     1.00ms /  494µs
+//   48 bits    4.42ms / 3.48ms     3.99ms / 1.59ms
+//   56 bits   14.52ms / 7.36ms    18.56ms / 6.37ms
+//   62 bits   52.00ms / 29.56ms   49.16ms / 18.21ms
+//
+// so find_divisor stops using squfof above 32 bits, where the two are about even.
+// hart's steps take the consecutive samples in tens of µs at every size
+
+use prime_factorization::{
+    collect_primes,
+    factor::{Method, divisor_by, find_divisor},
+    semiprime::factorize,
+};
+use std::{hint::black_box, time::Instant};
+
+// semiprimes per sample
+const SAMPLE: usize = 64;
+
+fn bench<T>(name: &str, runs: u32, f: impl Fn() -> T) {
+    // one warm-up run for the caches
+    black_box(f());
+    let started = Instant::now();
+    for _ in 0..runs {
+        black_box(f());
+    }
+    println!("{name:<48} {:>10.2?}", started.elapsed() / runs);
+}
+
+// the first prime from start on
+fn next_prime(start: u64) -> u64 {
+    collect_primes(start, start + 4096)[0]
+}
+
+// SAMPLE products of two primes with exactly `bits` bits. the primes are
+// consecutive for apart = None, otherwise log-uniform within `apart` bits either
+// side of bits / 2
+fn semiprimes(bits: u32, apart: Option<f64>) -> Vec<u64> {
+    let half = (bits as f64 - 0.5) / 2.0;
+    let primes = match apart {
+        None => (0..16)
+            .scan(2f64.powf(half) as u64, |p, _| {
+                *p = next_prime(*p + 1);
+                Some(*p)
+            })
+            .collect(),
+        Some(apart) => {
+            // xorshift, the same sample every run
+            let mut x: u64 = 0x9e37_79b9_7f4a_7c15;
+            (0..32)
+                .map(|_| {
+                    x ^= x << 13;
+                    x ^= x >> 7;
+                    x ^= x << 17;
+                    let u = (x >> 11) as f64 / (1u64 << 53) as f64;
+                    next_prime(2f64.powf(half + apart * (2.0 * u - 1.0)) as u64)
+                })
+                .collect()
+        }
+    };
+    let mut sample: Vec<u64> = factorize(primes)
+        .into_iter()
+        .filter(|&(n, p, q)| p != q && 128 - n.leading_zeros() == bits)
+        .map(|(n, _, _)| n as u64)
+        .collect();
+    sample.sort_unstable();
+    sample.truncate(SAMPLE);
+    sample
+}
+
+fn main() {
+    for bits in [20, 24, 28, 32, 40, 48, 56, 62] {
+        for (kind, apart) in [
+            ("consecutive", None),
+            ("balanced", Some(0.5)),
+            ("spread", Some(4.0)),
+        ] {
+            let sample = semiprimes(bits, apart);
+            let runs = if bits <= 32 { 20 } else { 1 };
+            for (name, method) in [
+                ("trial division", Method::TrialDivision),
+                ("hart", Method::Hart),
+                ("squfof", Method::Squfof),
+                ("rho", Method::Rho),
+            ] {
+                // trial division and hart take up to seconds per number up there
+                if method == Method::TrialDivision && bits > 32
+                    || method == Method::Hart && bits > 48 && apart.is_some()
+                {
+                    continue;
+                }
+                bench(&format!("{name}, {bits} bits {kind}"), runs, || {
+                    sample
+                        .iter()
+                        .filter_map(|&n| divisor_by(black_box(n), method))
+                        .count()
+                });
+            }
+            bench(&format!("find_divisor, {bits} bits {kind}"), runs, || {
+                sample
+                    .iter()
+                    .filter_map(|&n| find_divisor(black_box(n)))
+                    .count()
+            });
+        }
+    }
+}
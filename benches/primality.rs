@@ -0,0 +1,49 @@
+// cargo bench --bench primality
+//
+// compares the ways of testing a whole range against the sieve, and the
+// sequential against the parallel trial division of a single large prime
+
+use prime_factorization::{
+    Prime, collect_primes,
+    prime::{par_trial_division, trial_division},
+};
+use rayon::prelude::*;
+use std::{hint::black_box, time::Instant};
+
+const END: u64 = 10_000_000;
+
+fn bench<T>(name: &str, runs: u32, f: impl Fn() -> T) {
+    // one warm-up run for the thread pool and the caches
+    black_box(f());
+    let started = Instant::now();
+    for _ in 0..runs {
+        black_box(f());
+    }
+    println!("{name:<48} {:>10.2?}", started.elapsed() / runs);
+}
+
+fn main() {
+    bench("collect_primes(0, 10^7)", 10, || collect_primes(0, END));
+    bench("par filter Prime::prime, 0..10^7", 3, || {
+        (0..=END).into_par_iter().filter(|&n| n.prime()).count()
+    });
+    bench("par filter trial_division, 0..10^7", 3, || {
+        (0..=END)
+            .into_par_iter()
+            .filter(|&n| trial_division(n))
+            .count()
+    });
+    bench("sequential filter trial_division, 0..10^7", 3, || {
+        (0..=END).filter(|&n| trial_division(n)).count()
+    });
+
+    // 2^50 - 27, about 2.8 * 10^6 candidates up to √n
+    let p = (1 << 50) - 27;
+    bench("trial_division(2^50 - 27)", 5, || {
+        trial_division(black_box(p))
+    });
+    bench("par_trial_division(2^50 - 27)", 5, || {
+        par_trial_division(black_box(p))
+    });
+    bench("Prime::prime(2^50 - 27)", 1000, || black_box(p).prime());
+}
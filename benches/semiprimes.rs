@@ -0,0 +1,34 @@
+// cargo bench --bench semiprimes
+//
+// compares the heap merge of sorted_semiprimes against the parallel batches of
+// par_sorted_semiprimes on all pairs of the primes below a bound
+
+use prime_factorization::{
+    collect_primes,
+    semiprime::{par_sorted_semiprimes, sorted_semiprimes},
+};
+use std::{hint::black_box, time::Instant};
+
+fn bench<T>(name: &str, runs: u32, f: impl Fn() -> T) {
+    // one warm-up run for the thread pool and the caches
+    black_box(f());
+    let started = Instant::now();
+    for _ in 0..runs {
+        black_box(f());
+    }
+    println!("{name:<48} {:>10.2?}", started.elapsed() / runs);
+}
+
+fn main() {
+    for (bound, label) in [(10_000, "10^4"), (100_000, "10^5")] {
+        let primes = collect_primes(0, bound);
+        bench(&format!("sorted_semiprimes, primes < {label}"), 3, || {
+            sorted_semiprimes(&primes).fold(0u128, |sum, (n, _, _)| sum ^ n)
+        });
+        bench(
+            &format!("par_sorted_semiprimes, primes < {label}"),
+            3,
+            || par_sorted_semiprimes(&primes).fold(0u128, |sum, (n, _, _)| sum ^ n),
+        );
+    }
+}
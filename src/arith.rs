@@ -0,0 +1,143 @@
+//! Modular arithmetic and multiplicative functions.
+
+use crate::factor::factor;
+
+/// Greatest common divisor, `gcd(0, 0) = 0`.
+pub fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+/// [`gcd`] for `u128`.
+pub fn gcd_u128(mut a: u128, mut b: u128) -> u128 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+/// `a * b mod m`, widened to `u128` so the product never overflows.
+pub fn mul_mod(a: u64, b: u64, m: u64) -> u64 {
+    (a as u128 * b as u128 % m as u128) as u64
+}
+
+/// `base^exp mod m` by square and multiply.
+pub fn pow_mod(mut base: u64, mut exp: u64, m: u64) -> u64 {
+    let mut result = 1 % m;
+    base %= m;
+    while exp > 0 {
+        if exp & 1 == 1 {
+            result = mul_mod(result, base, m);
+        }
+        base = mul_mod(base, base, m);
+        exp >>= 1;
+    }
+    result
+}
+
+/// Euler's totient φ(n), the count of 1 <= k <= n coprime to n, φ(0) = 0.
+pub fn euler_phi(n: u64) -> u64 {
+    if n == 0 {
+        return 0;
+    }
+    // φ(p^e) = p^(e-1) * (p - 1)
+    factor(n)
+        .iter()
+        .map(|&(p, exp)| p.pow(exp - 1) * (p - 1))
+        .product()
+}
+
+/// Number of divisors τ(n), τ(0) = 0.
+pub fn divisor_count(n: u64) -> u64 {
+    if n == 0 {
+        return 0;
+    }
+    factor(n).iter().map(|&(_, exp)| exp as u64 + 1).product()
+}
+
+/// Sum of divisors σ(n), σ(0) = 0.
+///
+/// σ(n) can exceed `u64::MAX` for large `n`, so it is returned as `u128`.
+pub fn divisor_sum(n: u64) -> u128 {
+    if n == 0 {
+        return 0;
+    }
+    // σ(p^e) = 1 + p + ... + p^e
+    factor(n)
+        .iter()
+        .map(|&(p, exp)| (0..=exp).map(|i| (p as u128).pow(i)).sum::<u128>())
+        .product()
+}
+
+/// Möbius function μ(n): 0 if n has a square factor, otherwise (-1)^k for k prime factors.
+///
+/// μ(0) is undefined and returned as 0.
+pub fn mobius(n: u64) -> i8 {
+    if n == 0 {
+        return 0;
+    }
+    let factors = factor(n);
+    if factors.iter().any(|&(_, exp)| exp > 1) {
+        return 0;
+    }
+    if factors.len().is_multiple_of(2) {
+        1
+    } else {
+        -1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pow_mod_near_max() {
+        let m = u64::MAX - 58; // prime
+        assert_eq!(1, pow_mod(m - 1, 2, m));
+        assert_eq!(1, pow_mod(12345, m - 1, m));
+        assert_eq!(0, pow_mod(7, 0, 1));
+    }
+
+    #[test]
+    fn gcds() {
+        assert_eq!(0, gcd(0, 0));
+        assert_eq!(5, gcd(0, 5));
+        assert_eq!(6, gcd(12, 18));
+        assert_eq!(1, gcd(u64::MAX, u64::MAX - 1));
+    }
+
+    #[test]
+    fn multiplicative_functions() {
+        // brute force over the definitions
+        for n in 1..500u64 {
+            let divisors: Vec<u64> = (1..=n).filter(|d| n.is_multiple_of(*d)).collect();
+            assert_eq!(divisors.len() as u64, divisor_count(n));
+            assert_eq!(
+                divisors.iter().map(|&d| d as u128).sum::<u128>(),
+                divisor_sum(n)
+            );
+            assert_eq!(
+                (1..=n).filter(|&k| gcd(k, n) == 1).count() as u64,
+                euler_phi(n)
+            );
+            // Σ μ(d) over all divisors is 1 for n = 1 and 0 otherwise
+            let sum: i64 = divisors.iter().map(|&d| mobius(d) as i64).sum();
+            assert_eq!(if n == 1 { 1 } else { 0 }, sum);
+        }
+    }
+
+    #[test]
+    fn multiplicative_functions_large() {
+        // 2^64 - 1 = 3 * 5 * 17 * 257 * 641 * 65537 * 6700417
+        assert_eq!(128, divisor_count(u64::MAX));
+        assert_eq!(-1, mobius(u64::MAX));
+        assert_eq!(
+            2 * 4 * 16 * 256 * 640 * 65536 * 6700416,
+            euler_phi(u64::MAX)
+        );
+        assert!(divisor_sum(u64::MAX) > u64::MAX as u128);
+    }
+}
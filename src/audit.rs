@@ -0,0 +1,163 @@
+//! Weak RSA moduli.
+//!
+//! An RSA modulus `n = pq` is only as strong as its weakest prime choice. [`audit`]
+//! looks for the three that broken key generators produce most often, in order of
+//! their cost:
+//!
+//! - a small prime factor, by trial division up to 2^16
+//! - two primes close to each other, by 2^16 steps of Fermat's method, which covers
+//!   `q - p` up to about `2^10 n^(1/4)`
+//! - a prime `p` with a smooth `p − 1`, by Pollard's p − 1 with bounds of 10^5 and
+//!   10^6
+//!
+//! A strong 2048 bit modulus takes about a second, most of it in p − 1.
+//!
+//! ```
+//! use prime_factorization::{audit::{Weakness, audit}, bigint::BigUint};
+//!
+//! let p: BigUint = "1000000000000000000000000000057".parse().unwrap();
+//! let q: BigUint = "1000000000000000000000000000099".parse().unwrap();
+//! let finding = audit(&(&p * &q)).unwrap();
+//! assert_eq!(Weakness::ClosePrimes, finding.weakness);
+//! assert_eq!((p, q), (finding.p, finding.q));
+//! ```
+
+use crate::{
+    Prime, bigint::BigUint, collect_primes, fermat::fermat, montgomery::BigMontgomery,
+    smooth::p_minus_one,
+};
+use std::fmt;
+
+// trial division up to this bound
+const SMALL_FACTOR_BOUND: u64 = 1 << 16;
+
+const FERMAT_STEPS: u64 = 1 << 16;
+
+// p - 1 bounds, stage 2 takes about as long as stage 1
+const B1: u64 = 100_000;
+const B2: u64 = 1_000_000;
+
+/// Why a modulus is easy to factor.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Weakness {
+    /// A prime factor below 2^16.
+    SmallFactor,
+    /// Two factors close to `√n`, found by Fermat's method.
+    ClosePrimes,
+    /// A prime factor `p` whose `p − 1` has only small prime factors, found by
+    /// Pollard's p − 1.
+    SmoothPMinusOne,
+}
+
+impl fmt::Display for Weakness {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Weakness::SmallFactor => "small factor",
+            Weakness::ClosePrimes => "close primes",
+            Weakness::SmoothPMinusOne => "smooth p-1",
+        })
+    }
+}
+
+/// A weakness of a modulus `n` and the factors it gave away.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Finding {
+    /// What gave the factors away.
+    pub weakness: Weakness,
+    /// The smaller factor, a prime for [`Weakness::SmallFactor`].
+    pub p: BigUint,
+    /// `n / p`, not necessarily prime if `n` has more than two prime factors.
+    pub q: BigUint,
+}
+
+/// The first weakness of `n`, with the factors it gave away.
+///
+/// `None` if none of them turned up, always for primes and below 4. A prime `n`
+/// returns at once, without running the searches.
+pub fn audit(n: &BigUint) -> Option<Finding> {
+    if n.prime() {
+        return None;
+    }
+    let found = |weakness, d: BigUint| {
+        let q = n / &d;
+        let (p, q) = if d <= q { (d, q) } else { (q, d) };
+        Finding { weakness, p, q }
+    };
+
+    if let Some(p) = collect_primes(0, SMALL_FACTOR_BOUND)
+        .into_iter()
+        .find(|&p| n.rem_u64(p) == 0 && BigUint::from(p) < *n)
+    {
+        return Some(found(Weakness::SmallFactor, BigUint::from(p)));
+    }
+    // 0, 1, and 2 which is no factor of itself
+    if n.is_even() || n.is_one() {
+        return None;
+    }
+
+    if let Some(d) = fermat(n, FERMAT_STEPS) {
+        return Some(found(Weakness::ClosePrimes, d));
+    }
+    p_minus_one(&BigMontgomery::new(n), B1, B2).map(|d| found(Weakness::SmoothPMinusOne, d))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn big(n: u128) -> BigUint {
+        BigUint::from(n)
+    }
+
+    fn weakness(n: u128) -> Option<Weakness> {
+        audit(&big(n)).map(|finding| finding.weakness)
+    }
+
+    #[test]
+    fn weaknesses() {
+        let finding = audit(&big(65_521 * 18_446_744_073_709_551_557)).unwrap();
+        assert_eq!(Weakness::SmallFactor, finding.weakness);
+        assert_eq!(
+            (big(65_521), big(18_446_744_073_709_551_557)),
+            (finding.p, finding.q)
+        );
+        assert_eq!(Some(Weakness::SmallFactor), weakness(2 * 1_000_000_007));
+
+        // q is the next prime after p
+        let (p, q) = (18_446_744_073_709_551_557, 18_446_744_073_709_551_629);
+        let finding = audit(&big(p * q)).unwrap();
+        assert_eq!(Weakness::ClosePrimes, finding.weakness);
+        assert_eq!((big(p), big(q)), (finding.p, finding.q));
+
+        // 1_000_001_269 - 1 = 2^2 * 3^3 * 7 * 17^2 * 23 * 199
+        let finding = audit(&big(1_000_001_269 * 18_446_744_073_709_551_557)).unwrap();
+        assert_eq!(Weakness::SmoothPMinusOne, finding.weakness);
+        assert_eq!(big(1_000_001_269), finding.p);
+    }
+
+    #[test]
+    fn sound_moduli() {
+        // p - 1 = 2 * 500_000_003 and q - 1 = 2^2 * 11 * 137 * 547 * 5_594_472_617_641
+        assert_eq!(None, weakness(1_000_000_007 * 18_446_744_073_709_551_557));
+        for n in [
+            0,
+            1,
+            2,
+            3,
+            65_521,
+            1_000_000_007,
+            18_446_744_073_709_551_557,
+        ] {
+            assert_eq!(None, weakness(n), "{n}");
+        }
+    }
+
+    #[test]
+    fn primes_return_early() {
+        // 2^1279 - 1 would take seconds of p - 1 in a debug build
+        let mersenne = &(&BigUint::from(1u64) << 1279) - &BigUint::from(1u64);
+        let started = std::time::Instant::now();
+        assert_eq!(None, audit(&mersenne));
+        assert!(started.elapsed() < std::time::Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,202 @@
+//! Shared prime factors across many moduli, by Bernstein's batch gcd.
+//!
+//! Two moduli that share a prime give it away to a single gcd, but a corpus of `k`
+//! moduli has `k^2 / 2` pairs. The batch gcd multiplies all moduli up a
+//! [`product_tree`] and reduces the product back down it modulo the squares of the
+//! nodes, which leaves `P mod n^2` for every modulus `n` of the product `P`. Then
+//! `gcd(n, (P mod n^2) / n)` is the gcd of `n` with the product of all the others.
+//!
+//! Bernstein's quasi-linear bound needs FFT multiplication. [`BigUint`] multiplies
+//! large numbers by Karatsuba's method and divides them through Newton's iteration,
+//! so the top of the trees dominates and the time grows with the 1.6th power of the
+//! total size of the moduli: on a single core 1000 moduli of 1024 bits take 0.6
+//! seconds instead of half a million gcds, 4000 take 5.5 and 16000 take 45, see
+//! benches/bigint.rs.
+//!
+//! ```
+//! use prime_factorization::{batch::shared_factors, bigint::BigUint};
+//!
+//! let moduli: Vec<BigUint> = [11u64 * 13, 17 * 19, 13 * 23].map(BigUint::from).to_vec();
+//! assert_eq!(
+//!     vec![
+//!         (0, BigUint::from(11u64), BigUint::from(13u64)),
+//!         (2, BigUint::from(13u64), BigUint::from(23u64)),
+//!     ],
+//!     shared_factors(&moduli)
+//! );
+//! ```
+
+use crate::bigint::BigUint;
+use rayon::prelude::*;
+
+/// The levels of the product tree over `leaves`, from the leaves up to the product of
+/// all of them. Every node is the product of two nodes of the level below, the last
+/// node of a level of odd length moves up unchanged. Empty for no leaves.
+///
+/// ```
+/// use prime_factorization::{batch::product_tree, bigint::BigUint};
+///
+/// let tree = product_tree(&[2u64, 3, 5].map(BigUint::from));
+/// let levels: Vec<Vec<String>> = tree
+///     .iter()
+///     .map(|level| level.iter().map(BigUint::to_string).collect())
+///     .collect();
+/// assert_eq!(vec![vec!["2", "3", "5"], vec!["6", "5"], vec!["30"]], levels);
+/// ```
+pub fn product_tree(leaves: &[BigUint]) -> Vec<Vec<BigUint>> {
+    if leaves.is_empty() {
+        return Vec::new();
+    }
+    let mut tree = vec![leaves.to_vec()];
+    while tree[tree.len() - 1].len() > 1 {
+        let level: Vec<BigUint> = tree[tree.len() - 1]
+            .par_chunks(2)
+            .map(|pair| match pair {
+                [a, b] => a * b,
+                [a] => a.clone(),
+                _ => unreachable!("chunks of 2"),
+            })
+            .collect();
+        tree.push(level);
+    }
+    tree
+}
+
+/// For every modulus, its gcd with the product of all the other moduli.
+///
+/// 1 for a modulus that shares no prime, the modulus itself if every one of its
+/// primes turns up in the others, as for a modulus given twice.
+///
+/// # Panics
+///
+/// If a modulus is 0.
+pub fn batch_gcd(moduli: &[BigUint]) -> Vec<BigUint> {
+    let tree = product_tree(moduli);
+    let Some((root, levels)) = tree.split_last() else {
+        return Vec::new();
+    };
+
+    // the product modulo the square of every node, from the root down to the leaves
+    let mut remainders = root.clone();
+    for level in levels.iter().rev() {
+        remainders = level
+            .par_iter()
+            .enumerate()
+            .map(|(i, node)| &remainders[i / 2] % &(node * node))
+            .collect();
+    }
+    remainders
+        .par_iter()
+        .zip(moduli)
+        .map(|(r, n)| (r / n).gcd(n))
+        .collect()
+}
+
+/// The moduli that share a prime with another one, as `(index, p, q)` with
+/// `p * q = moduli[index]` and `p <= q`, in the order of the moduli.
+///
+/// A modulus all of whose primes turn up in others is split by its gcds with them one
+/// by one. Left out are the moduli this does not split either, such as a modulus
+/// given twice that shares no prime with a third.
+///
+/// # Panics
+///
+/// If a modulus is 0.
+pub fn shared_factors(moduli: &[BigUint]) -> Vec<(usize, BigUint, BigUint)> {
+    let gcds = batch_gcd(moduli);
+    let sharing: Vec<usize> = (0..moduli.len()).filter(|&i| !gcds[i].is_one()).collect();
+
+    sharing
+        .par_iter()
+        .filter_map(|&i| {
+            let n = &moduli[i];
+            let d = if gcds[i] != *n {
+                gcds[i].clone()
+            } else {
+                // every other modulus that shares a prime with n is among them
+                sharing
+                    .iter()
+                    .map(|&j| n.gcd(&moduli[j]))
+                    .find(|d| !d.is_one() && d != n)?
+            };
+            let q = n / &d;
+            Some(if d <= q { (i, d, q) } else { (i, q, d) })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn big(n: u128) -> BigUint {
+        BigUint::from(n)
+    }
+
+    #[test]
+    fn trees() {
+        assert!(product_tree(&[]).is_empty());
+        assert_eq!(vec![vec![big(7)]], product_tree(&[big(7)]));
+        let leaves: Vec<BigUint> = (2..=9).map(big).collect();
+        let tree = product_tree(&leaves);
+        assert_eq!(4, tree.len());
+        assert_eq!(vec![big(362_880)], tree[3]);
+        assert_eq!(vec![big(2 * 3 * 4 * 5), big(6 * 7 * 8 * 9)], tree[2]);
+    }
+
+    #[test]
+    fn gcds_match_pairwise() {
+        // the products of pairs of a few primes, some sharing and some not
+        let primes = [
+            1_000_003u128,
+            1_000_033,
+            1_000_037,
+            1_000_039,
+            1_000_081,
+            1_000_099,
+        ];
+        let moduli: Vec<BigUint> = [(0, 1), (2, 3), (1, 4), (5, 5), (0, 4), (3, 2)]
+            .iter()
+            .map(|&(i, j)| big(primes[i] * primes[j]))
+            .collect();
+        let gcds = batch_gcd(&moduli);
+        for (i, n) in moduli.iter().enumerate() {
+            let others = moduli
+                .iter()
+                .enumerate()
+                .filter(|&(j, _)| j != i)
+                .fold(BigUint::one(), |product, (_, m)| &product * m);
+            assert_eq!(n.gcd(&others), gcds[i], "{n}");
+        }
+        assert!(gcds[3].is_one());
+        assert_eq!(moduli[1], gcds[1]);
+        assert!(batch_gcd(&[]).is_empty());
+        assert_eq!(vec![big(1)], batch_gcd(&[big(15)]));
+    }
+
+    #[test]
+    fn shared_primes() {
+        let (p, q, r, s) = (1_000_003u128, 1_000_033, 1_000_037, 1_000_039);
+        let moduli = [big(p * q), big(r * s), big(q * r), big(p * s), big(7 * 11)];
+        // every prime of the first four moduli turns up twice
+        assert_eq!(
+            vec![
+                (0, big(p), big(q)),
+                (1, big(r), big(s)),
+                (2, big(q), big(r)),
+                (3, big(p), big(s)),
+            ],
+            shared_factors(&moduli)
+        );
+        // a modulus given twice has nothing to give away, unless a third shares a prime
+        assert!(shared_factors(&[big(p * q), big(p * q)]).is_empty());
+        assert_eq!(
+            vec![
+                (0, big(p), big(q)),
+                (1, big(p), big(q)),
+                (2, big(q), big(r))
+            ],
+            shared_factors(&[big(p * q), big(p * q), big(q * r)])
+        );
+    }
+}
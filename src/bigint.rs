@@ -0,0 +1,925 @@
+//! Arbitrary-precision unsigned integers.
+//!
+//! Just enough arithmetic for primality tests and factoring methods on numbers of
+//! a few hundred digits: schoolbook multiplication, Knuth's long division and
+//! modular exponentiation on 64 bit limbs. The products and quotients of the batch
+//! gcd run to millions of digits, so from 64 limbs up multiplication splits the
+//! factors by Karatsuba's method, and from 768 limbs up division multiplies by a
+//! reciprocal from Newton's iteration, in a few times the time of a product.
+//!
+//! ```
+//! use prime_factorization::bigint::BigUint;
+//!
+//! let m: BigUint = "170141183460469231731687303715884105727".parse().unwrap();
+//! let n = &(&m * &m) + &BigUint::from(1u64);
+//! assert_eq!("28948022309329048855892746252171976962977213799489202546401021394546514198530", n.to_string());
+//! assert_eq!(BigUint::from(1u64), &n % &m);
+//! ```
+
+use std::{
+    cmp::Ordering,
+    fmt,
+    ops::{Add, Div, Mul, Rem, Shl, Shr, Sub},
+    str::FromStr,
+};
+
+// multiplication goes from schoolbook to karatsuba at this many limbs of the shorter
+// factor, see benches/bigint.rs
+const KARATSUBA_LIMBS: usize = 64;
+
+// division goes from knuth's algorithm to newton's reciprocal at this many limbs of
+// both the divisor and the quotient
+const NEWTON_LIMBS: usize = 768;
+
+/// An unsigned integer of any size.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct BigUint {
+    // little endian, without high zero limbs, so zero has no limbs
+    limbs: Vec<u64>,
+}
+
+impl BigUint {
+    /// 0.
+    pub fn zero() -> Self {
+        BigUint { limbs: Vec::new() }
+    }
+
+    /// 1.
+    pub fn one() -> Self {
+        BigUint { limbs: vec![1] }
+    }
+
+    pub(crate) fn from_limbs(mut limbs: Vec<u64>) -> Self {
+        while limbs.last() == Some(&0) {
+            limbs.pop();
+        }
+        BigUint { limbs }
+    }
+
+    // little endian, without high zero limbs
+    pub(crate) fn limbs(&self) -> &[u64] {
+        &self.limbs
+    }
+
+    /// Whether this is 0.
+    pub fn is_zero(&self) -> bool {
+        self.limbs.is_empty()
+    }
+
+    /// Whether this is 1.
+    pub fn is_one(&self) -> bool {
+        self.limbs == [1]
+    }
+
+    /// Whether this is divisible by 2.
+    pub fn is_even(&self) -> bool {
+        self.limbs.first().is_none_or(|&low| low & 1 == 0)
+    }
+
+    /// Number of significant bits, 0 for 0.
+    pub fn bits(&self) -> u64 {
+        match self.limbs.last() {
+            Some(&high) => self.limbs.len() as u64 * 64 - high.leading_zeros() as u64,
+            None => 0,
+        }
+    }
+
+    /// Number of trailing zero bits, 0 for 0.
+    pub fn trailing_zeros(&self) -> u64 {
+        match self.limbs.iter().position(|&limb| limb != 0) {
+            Some(i) => i as u64 * 64 + self.limbs[i].trailing_zeros() as u64,
+            None => 0,
+        }
+    }
+
+    /// Whether bit `i` is set, counting from the least significant bit.
+    pub fn bit(&self, i: u64) -> bool {
+        self.limbs
+            .get((i / 64) as usize)
+            .is_some_and(|&limb| limb >> (i % 64) & 1 == 1)
+    }
+
+    /// The value as `u64`, if it fits.
+    pub fn to_u64(&self) -> Option<u64> {
+        match self.limbs[..] {
+            [] => Some(0),
+            [low] => Some(low),
+            _ => None,
+        }
+    }
+
+    /// The value as `u128`, if it fits.
+    pub fn to_u128(&self) -> Option<u128> {
+        match self.limbs[..] {
+            [] => Some(0),
+            [low] => Some(low as u128),
+            [low, high] => Some((high as u128) << 64 | low as u128),
+            _ => None,
+        }
+    }
+
+    /// `self - rhs`, or `None` if `rhs` is larger.
+    pub fn checked_sub(&self, rhs: &BigUint) -> Option<BigUint> {
+        if *self < *rhs {
+            return None;
+        }
+        let mut limbs = self.limbs.clone();
+        let mut borrow = false;
+        for (i, limb) in limbs.iter_mut().enumerate() {
+            let r = rhs.limbs.get(i).copied().unwrap_or(0);
+            if r == 0 && !borrow && i >= rhs.limbs.len() {
+                break;
+            }
+            let (d, b1) = limb.overflowing_sub(r);
+            let (d, b2) = d.overflowing_sub(borrow as u64);
+            *limb = d;
+            borrow = b1 || b2;
+        }
+        Some(BigUint::from_limbs(limbs))
+    }
+
+    /// `|self - other|`.
+    pub fn abs_diff(&self, other: &BigUint) -> BigUint {
+        if self >= other {
+            self - other
+        } else {
+            other - self
+        }
+    }
+
+    /// Quotient and remainder of the division by a single limb.
+    ///
+    /// # Panics
+    ///
+    /// If `d` is 0.
+    pub fn div_rem_u64(&self, d: u64) -> (BigUint, u64) {
+        assert!(d != 0, "division by zero");
+        let mut quotient = vec![0; self.limbs.len()];
+        let mut rem = 0u128;
+        for (i, &limb) in self.limbs.iter().enumerate().rev() {
+            let cur = rem << 64 | limb as u128;
+            quotient[i] = (cur / d as u128) as u64;
+            rem = cur % d as u128;
+        }
+        (BigUint::from_limbs(quotient), rem as u64)
+    }
+
+    /// `self mod d` for a single limb.
+    pub fn rem_u64(&self, d: u64) -> u64 {
+        assert!(d != 0, "division by zero");
+        self.limbs
+            .iter()
+            .rev()
+            .fold(0u128, |rem, &limb| (rem << 64 | limb as u128) % d as u128) as u64
+    }
+
+    /// Quotient and remainder, by Knuth's algorithm D, and for large divisors and
+    /// quotients by multiplying with the reciprocal of `d` from Newton's iteration.
+    ///
+    /// # Panics
+    ///
+    /// If `d` is 0.
+    pub fn div_rem(&self, d: &BigUint) -> (BigUint, BigUint) {
+        assert!(!d.is_zero(), "division by zero");
+        if self < d {
+            return (BigUint::zero(), self.clone());
+        }
+        if let [d] = d.limbs[..] {
+            let (q, r) = self.div_rem_u64(d);
+            return (q, BigUint::from(r));
+        }
+        if d.limbs.len() >= NEWTON_LIMBS && self.limbs.len() - d.limbs.len() >= NEWTON_LIMBS {
+            return self.div_rem_newton(d);
+        }
+        self.div_rem_knuth(d)
+    }
+
+    // knuth's algorithm D for self >= d and d of at least two limbs
+    fn div_rem_knuth(&self, d: &BigUint) -> (BigUint, BigUint) {
+        // shift so the top limb of the divisor has its high bit set,
+        // then every estimated quotient digit is off by at most 2
+        let shift = d.limbs.last().unwrap().leading_zeros();
+        let v = (d << shift).limbs;
+        let mut u = (self << shift).limbs;
+        u.resize(self.limbs.len() + 1, 0);
+
+        let n = v.len();
+        let m = u.len() - n;
+        let (v_top, v_next) = (v[n - 1] as u128, v[n - 2] as u128);
+        let mut quotient = vec![0u64; m];
+
+        for j in (0..m).rev() {
+            let top = (u[j + n] as u128) << 64 | u[j + n - 1] as u128;
+            let mut qhat = top / v_top;
+            let mut rhat = top % v_top;
+            while qhat > u64::MAX as u128 || qhat * v_next > (rhat << 64 | u[j + n - 2] as u128) {
+                qhat -= 1;
+                rhat += v_top;
+                if rhat > u64::MAX as u128 {
+                    break;
+                }
+            }
+
+            // u[j..=j + n] -= qhat * v
+            let mut carry = 0u128;
+            let mut borrow = false;
+            for i in 0..n {
+                let product = qhat * v[i] as u128 + carry;
+                carry = product >> 64;
+                let (d, b1) = u[i + j].overflowing_sub(product as u64);
+                let (d, b2) = d.overflowing_sub(borrow as u64);
+                u[i + j] = d;
+                borrow = b1 || b2;
+            }
+            let (d, b1) = u[j + n].overflowing_sub(carry as u64);
+            let (d, b2) = d.overflowing_sub(borrow as u64);
+            u[j + n] = d;
+
+            // qhat was one too large, add v back
+            if b1 || b2 {
+                qhat -= 1;
+                let mut carry = 0u128;
+                for i in 0..n {
+                    let sum = u[i + j] as u128 + v[i] as u128 + carry;
+                    u[i + j] = sum as u64;
+                    carry = sum >> 64;
+                }
+                u[j + n] = u[j + n].wrapping_add(carry as u64);
+            }
+            quotient[j] = qhat as u64;
+        }
+
+        u.truncate(n);
+        (
+            BigUint::from_limbs(quotient),
+            &BigUint::from_limbs(u) >> shift,
+        )
+    }
+
+    // long division in digits of as many bits as d has, each found from the
+    // reciprocal of d by barrett's method
+    fn div_rem_newton(&self, d: &BigUint) -> (BigUint, BigUint) {
+        let n = d.bits() as u32;
+        let v = d.reciprocal();
+        let digits = self.bits().div_ceil(n as u64) as u32;
+        let (mut q, mut r) = (BigUint::zero(), BigUint::zero());
+        for i in (0..digits).rev() {
+            // r < d, so cur < 2^(2n) and the estimate is at most 7 too small
+            let cur = &(&r << n) + &(self >> (i * n)).low_bits(n);
+            let mut digit = &(&(&cur >> (n - 1)) * &v) >> (n + 1);
+            r = &cur - &(&digit * d);
+            while r >= *d {
+                digit = &digit + &BigUint::one();
+                r = &r - d;
+            }
+            q = &(&q << n) + &digit;
+        }
+        (q, r)
+    }
+
+    // floor(2^(2n) / self) - c for self of n bits and some c from 0 to 4, from the
+    // reciprocal x of the top m bits by one step of newton's iteration
+    // x' = x + x (2^(2n) - self x) / 2^(2n), which doubles the correct bits. only the
+    // top bits of the error term count, and both roundings stay below the reciprocal
+    fn reciprocal(&self) -> BigUint {
+        let n = self.bits() as u32;
+        if self.limbs.len() < NEWTON_LIMBS {
+            return (&BigUint::one() << (2 * n)).div_rem_knuth(self).0;
+        }
+        // x is off by a factor below 1 + 2^(4 - m), newton leaves less than 1 of that
+        let m = n / 2 + 5;
+        let (s, t) = (n - m, m - 3);
+        let x = (self >> s).reciprocal();
+        let product = self * &x;
+        let power = &BigUint::one() << (2 * n - s);
+        match power.checked_sub(&product) {
+            Some(e) => &(&x << s) + &(&(&x * &(&e >> t)) >> (2 * m - t)),
+            None => {
+                let e = &(&(&product - &power) >> t) + &BigUint::one();
+                &(&x << s) - &(&(&(&x * &e) >> (2 * m - t)) + &BigUint::one())
+            }
+        }
+    }
+
+    /// `self^exp`.
+    pub fn pow(&self, mut exp: u32) -> BigUint {
+        let mut base = self.clone();
+        let mut result = BigUint::one();
+        while exp > 0 {
+            if exp & 1 == 1 {
+                result = &result * &base;
+            }
+            exp >>= 1;
+            if exp > 0 {
+                base = &base * &base;
+            }
+        }
+        result
+    }
+
+    /// Largest `r` with `r * r <= self`, by Newton's iteration.
+    pub fn isqrt(&self) -> BigUint {
+        if self.is_zero() {
+            return BigUint::zero();
+        }
+        // start above the root and descend, the iteration never undershoots
+        let mut x = &BigUint::one() << self.bits().div_ceil(2) as u32;
+        loop {
+            let next = &(&x + &(self / &x)) >> 1;
+            if next >= x {
+                return x;
+            }
+            x = next;
+        }
+    }
+
+    /// `self^exp mod m` by square and multiply.
+    ///
+    /// # Panics
+    ///
+    /// If `m` is 0.
+    pub fn pow_mod(&self, exp: &BigUint, m: &BigUint) -> BigUint {
+        let mut result = &BigUint::one() % m;
+        let base = self % m;
+        for i in (0..exp.bits()).rev() {
+            result = &(&result * &result) % m;
+            if exp.bit(i) {
+                result = &(&result * &base) % m;
+            }
+        }
+        result
+    }
+
+    /// Greatest common divisor, `gcd(0, 0) = 0`.
+    pub fn gcd(&self, other: &BigUint) -> BigUint {
+        let (mut a, mut b) = (self.clone(), other.clone());
+        while !b.is_zero() {
+            let r = &a % &b;
+            (a, b) = (b, r);
+        }
+        a
+    }
+
+    /// Parses digits in the given radix, without sign or prefix.
+    pub fn from_str_radix(digits: &str, radix: u32) -> Option<BigUint> {
+        if digits.is_empty() || !(2..=36).contains(&radix) {
+            return None;
+        }
+        let mut n = BigUint::zero();
+        for c in digits.chars() {
+            let digit = c.to_digit(radix)?;
+            n.mul_add_small(radix as u64, digit as u64);
+        }
+        Some(n)
+    }
+
+    // self mod 2^bits
+    fn low_bits(&self, bits: u32) -> BigUint {
+        let whole = (bits / 64) as usize;
+        let mut limbs: Vec<u64> = self.limbs.iter().copied().take(whole + 1).collect();
+        if limbs.len() > whole {
+            limbs[whole] &= (1 << (bits % 64)) - 1;
+        }
+        BigUint::from_limbs(limbs)
+    }
+
+    // self = self * m + a
+    fn mul_add_small(&mut self, m: u64, a: u64) {
+        let mut carry = a as u128;
+        for limb in &mut self.limbs {
+            let cur = *limb as u128 * m as u128 + carry;
+            *limb = cur as u64;
+            carry = cur >> 64;
+        }
+        if carry > 0 {
+            self.limbs.push(carry as u64);
+        }
+    }
+}
+
+impl From<u64> for BigUint {
+    fn from(n: u64) -> Self {
+        BigUint::from_limbs(vec![n])
+    }
+}
+
+impl From<u128> for BigUint {
+    fn from(n: u128) -> Self {
+        BigUint::from_limbs(vec![n as u64, (n >> 64) as u64])
+    }
+}
+
+impl Ord for BigUint {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.limbs
+            .len()
+            .cmp(&other.limbs.len())
+            .then_with(|| self.limbs.iter().rev().cmp(other.limbs.iter().rev()))
+    }
+}
+
+impl PartialOrd for BigUint {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for BigUint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // peel off 19 decimal digits at a time, the largest power of 10 in a limb
+        const CHUNK: u64 = 10_000_000_000_000_000_000;
+        let mut chunks = Vec::new();
+        let mut n = self.clone();
+        while !n.is_zero() {
+            let (q, r) = n.div_rem_u64(CHUNK);
+            chunks.push(r);
+            n = q;
+        }
+
+        let mut digits = match chunks.pop() {
+            Some(high) => high.to_string(),
+            None => "0".to_string(),
+        };
+        for chunk in chunks.iter().rev() {
+            digits.push_str(&format!("{chunk:019}"));
+        }
+        f.pad_integral(true, "", &digits)
+    }
+}
+
+/// Error for a string that is not a decimal number.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseBigUintError;
+
+impl fmt::Display for ParseBigUintError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid digit found in string")
+    }
+}
+
+impl std::error::Error for ParseBigUintError {}
+
+impl FromStr for BigUint {
+    type Err = ParseBigUintError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        BigUint::from_str_radix(s, 10).ok_or(ParseBigUintError)
+    }
+}
+
+impl Add for &BigUint {
+    type Output = BigUint;
+
+    fn add(self, rhs: &BigUint) -> BigUint {
+        let (long, short) = if self.limbs.len() >= rhs.limbs.len() {
+            (self, rhs)
+        } else {
+            (rhs, self)
+        };
+        let mut limbs = long.limbs.clone();
+        let mut carry = false;
+        for (i, limb) in limbs.iter_mut().enumerate() {
+            let s = short.limbs.get(i).copied().unwrap_or(0);
+            if i >= short.limbs.len() && !carry {
+                break;
+            }
+            let (sum, c1) = limb.overflowing_add(s);
+            let (sum, c2) = sum.overflowing_add(carry as u64);
+            *limb = sum;
+            carry = c1 || c2;
+        }
+        if carry {
+            limbs.push(1);
+        }
+        BigUint::from_limbs(limbs)
+    }
+}
+
+impl Sub for &BigUint {
+    type Output = BigUint;
+
+    /// # Panics
+    ///
+    /// If `rhs` is larger than `self`.
+    fn sub(self, rhs: &BigUint) -> BigUint {
+        self.checked_sub(rhs)
+            .expect("attempt to subtract with overflow")
+    }
+}
+
+impl Mul for &BigUint {
+    type Output = BigUint;
+
+    fn mul(self, rhs: &BigUint) -> BigUint {
+        if self.is_zero() || rhs.is_zero() {
+            return BigUint::zero();
+        }
+        BigUint::from_limbs(mul_limbs(&self.limbs, &rhs.limbs))
+    }
+}
+
+// the product of two little endian limb slices, len(a) + len(b) limbs long
+fn mul_limbs(a: &[u64], b: &[u64]) -> Vec<u64> {
+    let (a, b) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+    if b.len() < KARATSUBA_LIMBS {
+        return schoolbook(a, b);
+    }
+    let mut limbs = vec![0u64; a.len() + b.len()];
+    if a.len() >= 2 * b.len() {
+        // pieces of a as long as b, so that karatsuba splits them evenly
+        for (i, piece) in a.chunks(b.len()).enumerate() {
+            add_at(&mut limbs, &mul_limbs(piece, b), i * b.len());
+        }
+        return limbs;
+    }
+
+    // (a1 B + a0)(b1 B + b0) = a1 b1 B^2 + ((a0 + a1)(b0 + b1) - a0 b0 - a1 b1) B + a0 b0
+    // for B = 2^(64 half), three products of half the size instead of four
+    let half = a.len() / 2;
+    let (a0, a1) = a.split_at(half);
+    let (b0, b1) = b.split_at(half);
+    let low = mul_limbs(a0, b0);
+    let high = mul_limbs(a1, b1);
+    let mut middle = mul_limbs(&add_limbs(a0, a1), &add_limbs(b0, b1));
+    sub_from(&mut middle, &low);
+    sub_from(&mut middle, &high);
+    add_at(&mut limbs, &low, 0);
+    add_at(&mut limbs, &high, 2 * half);
+    add_at(&mut limbs, &middle, half);
+    limbs
+}
+
+fn schoolbook(a: &[u64], b: &[u64]) -> Vec<u64> {
+    let mut limbs = vec![0u64; a.len() + b.len()];
+    for (i, &x) in a.iter().enumerate() {
+        let mut carry = 0u128;
+        for (j, &y) in b.iter().enumerate() {
+            let cur = x as u128 * y as u128 + limbs[i + j] as u128 + carry;
+            limbs[i + j] = cur as u64;
+            carry = cur >> 64;
+        }
+        limbs[i + b.len()] = carry as u64;
+    }
+    limbs
+}
+
+// a + b, one limb longer than the longer of them
+fn add_limbs(a: &[u64], b: &[u64]) -> Vec<u64> {
+    let (a, b) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+    let mut sum = a.to_vec();
+    sum.push(0);
+    add_at(&mut sum, b, 0);
+    sum
+}
+
+// acc += x * 2^(64 offset), the sum fits in acc
+fn add_at(acc: &mut [u64], x: &[u64], offset: usize) {
+    let mut carry = false;
+    for (i, limb) in acc[offset..].iter_mut().enumerate() {
+        let s = x.get(i).copied().unwrap_or(0);
+        if i >= x.len() && !carry {
+            break;
+        }
+        let (sum, c1) = limb.overflowing_add(s);
+        let (sum, c2) = sum.overflowing_add(carry as u64);
+        *limb = sum;
+        carry = c1 || c2;
+    }
+}
+
+// acc -= x, the difference is not negative
+fn sub_from(acc: &mut [u64], x: &[u64]) {
+    let mut borrow = false;
+    for (i, limb) in acc.iter_mut().enumerate() {
+        let s = x.get(i).copied().unwrap_or(0);
+        if i >= x.len() && !borrow {
+            break;
+        }
+        let (d, b1) = limb.overflowing_sub(s);
+        let (d, b2) = d.overflowing_sub(borrow as u64);
+        *limb = d;
+        borrow = b1 || b2;
+    }
+}
+
+impl Div for &BigUint {
+    type Output = BigUint;
+
+    fn div(self, rhs: &BigUint) -> BigUint {
+        self.div_rem(rhs).0
+    }
+}
+
+impl Rem for &BigUint {
+    type Output = BigUint;
+
+    fn rem(self, rhs: &BigUint) -> BigUint {
+        self.div_rem(rhs).1
+    }
+}
+
+impl Shl<u32> for &BigUint {
+    type Output = BigUint;
+
+    fn shl(self, shift: u32) -> BigUint {
+        if self.is_zero() {
+            return BigUint::zero();
+        }
+        let (whole, bits) = ((shift / 64) as usize, shift % 64);
+        let mut limbs = vec![0u64; whole];
+        if bits == 0 {
+            limbs.extend_from_slice(&self.limbs);
+        } else {
+            let mut carry = 0;
+            for &limb in &self.limbs {
+                limbs.push(limb << bits | carry);
+                carry = limb >> (64 - bits);
+            }
+            limbs.push(carry);
+        }
+        BigUint::from_limbs(limbs)
+    }
+}
+
+impl Shr<u32> for &BigUint {
+    type Output = BigUint;
+
+    fn shr(self, shift: u32) -> BigUint {
+        let (whole, bits) = ((shift / 64) as usize, shift % 64);
+        if whole >= self.limbs.len() {
+            return BigUint::zero();
+        }
+        let high = &self.limbs[whole..];
+        let limbs = if bits == 0 {
+            high.to_vec()
+        } else {
+            (0..high.len())
+                .map(|i| {
+                    let next = high.get(i + 1).map_or(0, |&limb| limb << (64 - bits));
+                    high[i] >> bits | next
+                })
+                .collect()
+        };
+        BigUint::from_limbs(limbs)
+    }
+}
+
+// owned operands forward to the borrowed implementations
+macro_rules! forward_owned {
+    ($($trait:ident $method:ident),*) => {$(
+        impl $trait for BigUint {
+            type Output = BigUint;
+
+            fn $method(self, rhs: BigUint) -> BigUint {
+                (&self).$method(&rhs)
+            }
+        }
+
+        impl $trait<&BigUint> for BigUint {
+            type Output = BigUint;
+
+            fn $method(self, rhs: &BigUint) -> BigUint {
+                (&self).$method(rhs)
+            }
+        }
+    )*};
+}
+
+forward_owned!(Add add, Sub sub, Mul mul, Div div, Rem rem);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn big(s: &str) -> BigUint {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn round_trips() {
+        for s in [
+            "0",
+            "1",
+            "18446744073709551615",
+            "18446744073709551616",
+            "340282366920938463463374607431768211455",
+            "340282366920938463463374607431768211456",
+            "10000000000000000000000000000000000000000000000000000000000000000000000000000000001",
+        ] {
+            assert_eq!(s, big(s).to_string());
+        }
+        assert_eq!(Some(u64::MAX), big("18446744073709551615").to_u64());
+        assert_eq!(None, big("18446744073709551616").to_u64());
+        assert_eq!(Some(u128::MAX), BigUint::from(u128::MAX).to_u128());
+        assert_eq!(
+            None,
+            (&BigUint::from(u128::MAX) + &BigUint::one()).to_u128()
+        );
+        assert_eq!(
+            Some(BigUint::from(255u64)),
+            BigUint::from_str_radix("ff", 16)
+        );
+        assert!("12a".parse::<BigUint>().is_err());
+        assert!("".parse::<BigUint>().is_err());
+        assert_eq!("  42", format!("{:>4}", BigUint::from(42u64)));
+    }
+
+    #[test]
+    fn arithmetic_matches_u128() {
+        let values = [
+            0u128,
+            1,
+            2,
+            u64::MAX as u128,
+            u64::MAX as u128 + 1,
+            1 << 100,
+            u128::MAX / 3,
+            u128::MAX,
+        ];
+        for &a in &values {
+            for &b in &values {
+                let (x, y) = (BigUint::from(a), BigUint::from(b));
+                assert_eq!(a.checked_sub(b).map(BigUint::from), x.checked_sub(&y));
+                if let Some(sum) = a.checked_add(b) {
+                    assert_eq!(BigUint::from(sum), &x + &y);
+                }
+                if let Some(product) = a.checked_mul(b) {
+                    assert_eq!(BigUint::from(product), &x * &y);
+                }
+                if let (Some(q), Some(r)) = (a.checked_div(b), a.checked_rem(b)) {
+                    assert_eq!(
+                        (BigUint::from(q), BigUint::from(r)),
+                        x.div_rem(&y),
+                        "{a} / {b}"
+                    );
+                }
+                assert_eq!(a.cmp(&b), x.cmp(&y));
+            }
+        }
+    }
+
+    #[test]
+    fn long_division() {
+        // (2^127 - 1) * (2^89 - 1) + 12345, divided both ways
+        let p = big("170141183460469231731687303715884105727");
+        let q = big("618970019642690137449562111");
+        let r = BigUint::from(12345u64);
+        let n = &(&p * &q) + &r;
+        assert_eq!((q.clone(), r.clone()), n.div_rem(&p));
+        assert_eq!((p.clone(), r.clone()), n.div_rem(&q));
+        assert_eq!((BigUint::one(), BigUint::zero()), n.div_rem(&n));
+        assert_eq!((BigUint::zero(), q.clone()), q.div_rem(&n));
+
+        // divisors whose quotient digit estimate needs the add back step
+        let d = &(&BigUint::one() << 128) - &BigUint::one();
+        let n = &(&d * &d) + &(&d - &BigUint::one());
+        assert_eq!((d.clone(), &d - &BigUint::one()), n.div_rem(&d));
+
+        for shift in [1, 63, 64, 65, 127, 200] {
+            let n = &BigUint::from(u128::MAX) << shift;
+            assert_eq!(BigUint::from(u128::MAX), &n >> shift);
+            assert_eq!(BigUint::zero(), &n % &(&BigUint::one() << shift));
+        }
+        assert_eq!(
+            7,
+            big("100000000000000000000000000000000000007").rem_u64(10)
+        );
+    }
+
+    // pseudo random limbs from a linear congruential generator
+    fn random(state: &mut u64, len: usize) -> BigUint {
+        let limbs = (0..len)
+            .map(|_| {
+                *state = state
+                    .wrapping_mul(6_364_136_223_846_793_005)
+                    .wrapping_add(1_442_695_040_888_963_407);
+                // mix in runs of all ones and zeros, where carries go wrong
+                match *state >> 60 {
+                    0 => 0,
+                    1 => u64::MAX,
+                    _ => *state,
+                }
+            })
+            .collect();
+        BigUint::from_limbs(limbs)
+    }
+
+    #[test]
+    fn division_identity() {
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let mut random = |len| random(&mut state, len);
+        for len in 1..12 {
+            for _ in 0..50 {
+                let a = random(len + 3);
+                let b = random(len);
+                if b.is_zero() {
+                    continue;
+                }
+                let (q, r) = a.div_rem(&b);
+                assert!(r < b);
+                assert_eq!(a, &(&q * &b) + &r);
+            }
+        }
+    }
+
+    #[test]
+    fn karatsuba() {
+        let mut state = 0x9e37_79b9_7f4a_7c15u64;
+        for (len_a, len_b) in [
+            (32, 32),
+            (33, 32),
+            (63, 40),
+            (100, 100),
+            (257, 129),
+            (500, 64),
+        ] {
+            let (a, b) = (random(&mut state, len_a), random(&mut state, len_b));
+            let expected = BigUint::from_limbs(schoolbook(&a.limbs, &b.limbs));
+            assert_eq!(expected, &a * &b, "{len_a} x {len_b} limbs");
+            assert_eq!(expected, &b * &a);
+        }
+        // all ones, where every carry of the middle product goes through
+        let ones = &(&BigUint::one() << (64 * 200)) - &BigUint::one();
+        assert_eq!(
+            &(&(&BigUint::one() << (128 * 200)) - &(&ones << 1)) - &BigUint::one(),
+            &ones * &ones
+        );
+    }
+
+    #[test]
+    fn newton_division() {
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        for (len_a, len_d) in [(256, 128), (300, 129), (600, 200), (1000, 250), (700, 500)] {
+            let (a, d) = (random(&mut state, len_a), random(&mut state, len_d));
+            let (q, r) = a.div_rem(&d);
+            assert_eq!(
+                a.div_rem_knuth(&d),
+                (q.clone(), r.clone()),
+                "{len_a} / {len_d}"
+            );
+            assert!(r < d);
+            assert_eq!(a, &(&q * &d) + &r);
+        }
+        // divisors just above and below a power of two, where the reciprocal is the
+        // furthest from its estimate
+        let power = &BigUint::one() << (64 * 200);
+        for d in [
+            &power - &BigUint::one(),
+            &power + &BigUint::one(),
+            &power >> 1,
+        ] {
+            let a = &(&d * &d) - &BigUint::one();
+            assert_eq!(a.div_rem_knuth(&d), a.div_rem(&d));
+            let power = &BigUint::one() << (2 * d.bits() as u32);
+            let (v, exact) = (d.reciprocal(), power.div_rem_knuth(&d).0);
+            assert!(v <= exact && &exact - &v <= BigUint::from(4u64));
+        }
+    }
+
+    #[test]
+    fn powers() {
+        let m = &(&BigUint::one() << 127) - &BigUint::one();
+        // fermat for the mersenne prime 2^127 - 1
+        assert_eq!(
+            BigUint::one(),
+            BigUint::from(3u64).pow_mod(&(&m - &BigUint::one()), &m)
+        );
+        assert_eq!(&BigUint::one() << 100, BigUint::from(2u64).pow(100));
+        assert_eq!(BigUint::one(), BigUint::from(7u64).pow(0));
+        assert_eq!(
+            BigUint::zero(),
+            BigUint::from(7u64).pow_mod(&m, &BigUint::one())
+        );
+        assert_eq!(
+            BigUint::from(1024u64 % 1000),
+            BigUint::from(2u64).pow_mod(&BigUint::from(10u64), &BigUint::from(1000u64))
+        );
+    }
+
+    #[test]
+    fn square_roots() {
+        for n in (0..10_000u128).chain([u64::MAX as u128, u128::MAX, 1 << 127]) {
+            assert_eq!(BigUint::from(n.isqrt()), BigUint::from(n).isqrt(), "√{n}");
+        }
+        let p = big("170141183460469231731687303715884105727");
+        let square = &p * &p;
+        assert_eq!(p, square.isqrt());
+        assert_eq!(&p - &BigUint::one(), (&square - &BigUint::one()).isqrt());
+        assert_eq!(p, (&square + &BigUint::one()).isqrt());
+    }
+
+    #[test]
+    fn gcds_and_bits() {
+        let p = big("170141183460469231731687303715884105727");
+        let q = big("618970019642690137449562111");
+        assert_eq!(q, (&p * &q).gcd(&(&q * &q)));
+        assert_eq!(BigUint::one(), p.gcd(&q));
+        assert_eq!(p, p.gcd(&BigUint::zero()));
+        assert_eq!(127, p.bits());
+        assert_eq!(0, BigUint::zero().bits());
+        assert_eq!(100, (&BigUint::one() << 100).trailing_zeros());
+        assert!((&BigUint::one() << 100).is_even());
+        assert!(!p.is_even());
+        assert!(BigUint::zero().is_even());
+    }
+}
@@ -0,0 +1,264 @@
+//! Baillie-PSW: a strong probable-prime test to base 2 followed by a strong Lucas
+//! probable-prime test with Selfridge's parameters.
+//!
+//! No composite passing both is known, and there is none below 2^64.
+
+use crate::{
+    bigint::BigUint,
+    montgomery::{BigMontgomery, Montgomery},
+    ring::Residues,
+    roots::{exact_sqrt_big, wide_isqrt},
+};
+
+// trial division before the expensive tests, also keeps D from sharing a factor with n
+const SMALL_PRIMES: [u64; 25] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+];
+
+pub(crate) fn bpsw_u128(n: u128) -> bool {
+    if let Some(small) = small_answer(n < 100 * 100, |p| n % p as u128, n as u64) {
+        return small;
+    }
+
+    let mont = Montgomery::new(n);
+    let s = (n - 1).trailing_zeros();
+    let d = (n - 1) >> s;
+    if !strong_probable_prime(&mont, &bits(d), s, mont.enter(2)) {
+        return false;
+    }
+
+    // n + 1 = d * 2^s, without overflowing for n close to 2^128
+    let half = (n >> 1) + 1;
+    let s = half.trailing_zeros() + 1;
+    let d = half >> (s - 1);
+    let root = wide_isqrt(n) as u128;
+    let square = root * root == n;
+    match selfridge(|m| (n % m as u128) as u64, square) {
+        Some((disc, q)) => strong_lucas(&mont, disc, q, &bits(d), s),
+        None => false,
+    }
+}
+
+pub(crate) fn bpsw_big(n: &BigUint) -> bool {
+    if let Some(n) = n.to_u128() {
+        return bpsw_u128(n);
+    }
+    if SMALL_PRIMES.iter().any(|&p| n.rem_u64(p) == 0) {
+        return false;
+    }
+
+    let ring = BigMontgomery::new(n);
+    let one = BigUint::one();
+    let minus_one = n - &one;
+    let s = minus_one.trailing_zeros();
+    let d = &minus_one >> s as u32;
+    if !strong_probable_prime(&ring, &big_bits(&d), s as u32, ring.residue(2)) {
+        return false;
+    }
+
+    let plus_one = n + &one;
+    let s = plus_one.trailing_zeros();
+    let d = &plus_one >> s as u32;
+    let square = exact_sqrt_big(n).is_some();
+    match selfridge(|m| n.rem_u64(m), square) {
+        Some((disc, q)) => strong_lucas(&ring, disc, q, &big_bits(&d), s as u32),
+        None => false,
+    }
+}
+
+// the answer for even, tiny or small-factor n, None if the real tests have to run
+fn small_answer(tiny: bool, rem: impl Fn(u64) -> u128, low: u64) -> Option<bool> {
+    for p in SMALL_PRIMES {
+        if rem(p) == 0 {
+            return Some(tiny && low == p);
+        }
+    }
+    // no factor below 100 means anything below 100^2 is prime
+    tiny.then_some(low >= 2)
+}
+
+// most significant bit first
+fn bits(n: u128) -> Vec<bool> {
+    (0..u128::BITS - n.leading_zeros())
+        .rev()
+        .map(|i| n >> i & 1 == 1)
+        .collect()
+}
+
+fn big_bits(n: &BigUint) -> Vec<bool> {
+    (0..n.bits()).rev().map(|i| n.bit(i)).collect()
+}
+
+// n - 1 = d * 2^s, a in the representation of the ring
+fn strong_probable_prime<R: Residues>(ring: &R, d: &[bool], s: u32, a: R::T) -> bool {
+    let one = ring.residue(1);
+    let minus_one = ring.residue(-1);
+
+    let mut x = one.clone();
+    for &bit in d {
+        x = ring.mul(&x, &x);
+        if bit {
+            x = ring.mul(&x, &a);
+        }
+    }
+    if x == one || x == minus_one {
+        return true;
+    }
+    for _ in 1..s {
+        x = ring.mul(&x, &x);
+        if x == minus_one {
+            return true;
+        }
+    }
+    false
+}
+
+// selfridge's method A: the first D in 5, -7, 9, -11, ... with (D / n) = -1, P = 1
+// and Q = (1 - D) / 4, or None if n turns out composite on the way
+fn selfridge(n_mod: impl Fn(u64) -> u64, square: bool) -> Option<(i64, i64)> {
+    let mut disc = 5i64;
+    loop {
+        match jacobi(disc, &n_mod) {
+            -1 => return Some((disc, (1 - disc) / 4)),
+            // n > 100^2 has no factor below 100, so |D| < n and D shares a factor with n
+            0 => return None,
+            _ => {}
+        }
+        // (D / n) is never -1 for a square, check once the first few D failed
+        if disc == -15 && square {
+            return None;
+        }
+        disc = if disc > 0 { -disc - 2 } else { -disc + 2 };
+    }
+}
+
+// U and V lucas sequences with n + 1 = d * 2^s, n passes if U_d = 0 or V_(d * 2^r) = 0
+fn strong_lucas<R: Residues>(ring: &R, disc: i64, q: i64, d: &[bool], s: u32) -> bool {
+    let zero = ring.residue(0);
+    let disc = ring.residue(disc);
+    let q = ring.residue(q);
+
+    // U_1 = 1, V_1 = P = 1, Q^1
+    let mut u = ring.residue(1);
+    let mut v = u.clone();
+    let mut qk = q.clone();
+    for &bit in &d[1..] {
+        // U_2k = U_k V_k, V_2k = V_k^2 - 2 Q^k
+        u = ring.mul(&u, &v);
+        v = ring.sub(&ring.mul(&v, &v), &ring.add(&qk, &qk));
+        qk = ring.mul(&qk, &qk);
+        if bit {
+            // U_k+1 = (P U_k + V_k) / 2, V_k+1 = (D U_k + P V_k) / 2
+            let next_u = ring.half(&ring.add(&u, &v));
+            v = ring.half(&ring.add(&ring.mul(&disc, &u), &v));
+            u = next_u;
+            qk = ring.mul(&qk, &q);
+        }
+    }
+
+    if u == zero || v == zero {
+        return true;
+    }
+    for _ in 1..s {
+        v = ring.sub(&ring.mul(&v, &v), &ring.add(&qk, &qk));
+        if v == zero {
+            return true;
+        }
+        qk = ring.mul(&qk, &qk);
+    }
+    false
+}
+
+// jacobi symbol (a / n) for odd n > |a|, given n mod m for small m
+fn jacobi(a: i64, n_mod: impl Fn(u64) -> u64) -> i32 {
+    let mut result = 1;
+    // (-1 / n) = -1 iff n = 3 mod 4
+    if a < 0 && n_mod(4) == 3 {
+        result = -result;
+    }
+    let mut a = a.unsigned_abs();
+    // (2 / n) = -1 iff n = 3, 5 mod 8
+    while a.is_multiple_of(2) {
+        a /= 2;
+        if matches!(n_mod(8), 3 | 5) {
+            result = -result;
+        }
+    }
+    if a == 1 {
+        return result;
+    }
+    // reciprocity, (a / n) = (n / a) unless both are 3 mod 4
+    if a % 4 == 3 && n_mod(4) == 3 {
+        result = -result;
+    }
+    result * small_jacobi(n_mod(a), a)
+}
+
+fn small_jacobi(mut a: u64, mut n: u64) -> i32 {
+    let mut result = 1;
+    while a != 0 {
+        while a.is_multiple_of(2) {
+            a /= 2;
+            if matches!(n % 8, 3 | 5) {
+                result = -result;
+            }
+        }
+        (a, n) = (n, a);
+        if a % 4 == 3 && n % 4 == 3 {
+            result = -result;
+        }
+        a %= n;
+    }
+    if n == 1 { result } else { 0 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Prime;
+
+    fn lucas_only(n: u128) -> bool {
+        let mont = Montgomery::new(n);
+        let half = (n >> 1) + 1;
+        let s = half.trailing_zeros() + 1;
+        let d = half >> (s - 1);
+        match selfridge(|m| (n % m as u128) as u64, false) {
+            Some((disc, q)) => strong_lucas(&mont, disc, q, &bits(d), s),
+            None => false,
+        }
+    }
+
+    #[test]
+    fn jacobi_symbols() {
+        // (a / 7) for a = 1..7: 1 1 -1 1 -1 -1
+        let expected = [1, 1, -1, 1, -1, -1, 0];
+        for (a, &j) in (1..=7).zip(&expected) {
+            assert_eq!(j, jacobi(a, |m| 7 % m), "({a} / 7)");
+            assert_eq!(j, small_jacobi(a as u64, 7));
+        }
+        assert_eq!(-1, jacobi(-1, |m| 7 % m));
+        assert_eq!(1, jacobi(5, |m| 11 % m));
+        assert_eq!(0, jacobi(15, |m| 45 % m));
+    }
+
+    #[test]
+    fn strong_lucas_pseudoprimes() {
+        // composites the strong lucas test alone lets through (OEIS A217255)
+        for n in [
+            5459, 5777, 10877, 16109, 18971, 22499, 24569, 25199, 40309, 58519,
+        ] {
+            assert!(lucas_only(n), "{n}");
+            assert!(!bpsw_u128(n), "{n}");
+        }
+        for p in [10007, 65537, 1_000_000_007, (1 << 61) - 1, (1 << 127) - 1] {
+            assert!(lucas_only(p), "{p}");
+        }
+    }
+
+    #[test]
+    fn matches_prime_below_100_000() {
+        for n in 0..100_000u64 {
+            assert_eq!(n.prime(), bpsw_u128(n as u128), "{n}");
+        }
+    }
+}
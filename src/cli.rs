@@ -0,0 +1,687 @@
+use crate::output::Format;
+use prime_factorization::{
+    Factor, FactorError, Prime,
+    bigint::BigUint,
+    expr,
+    factor::{Method, Pipeline},
+};
+use std::{fmt, path::PathBuf};
+
+// the prime list of the range has to fit into memory
+pub const MAX_PRIMES_RANGE: u64 = 1 << 34;
+// listing semiprimes additionally keeps one heap entry per prime
+pub const MAX_SEMIPRIMES_RANGE: u64 = 1 << 32;
+// the factor size of the default pipeline
+const DEFAULT_DIGITS: u32 = 20;
+// the factor sizes Stage::ecm has bounds for
+const DIGITS: std::ops::RangeInclusive<u32> = 15..=40;
+
+pub const USAGE: &str = "\
+calculate prime factors
+
+Usage:
+  prime_factorization [OPTIONS] <COMMAND>
+  prime_factorization [OPTIONS]              prompt for a range or a number
+
+Commands:
+  primes RANGE            list all primes in RANGE
+  semiprimes RANGE        list all products p * q of distinct primes p < q in RANGE
+  factor N...             prime factorization of every N
+  is-prime N...           check every N for primality
+  audit FILE              check the moduli in FILE, one per line, for small factors,
+                          close primes and smooth p-1, '-' reads stdin
+  batch-gcd FILE          factor the moduli in FILE that share a prime with another
+                          one, by a single batch gcd
+
+  factor and is-prime take numbers of any size up to 65536 bits, ranges are
+  limited to u64
+Numbers:
+  1000000  1_000_000  0xFFFF_FFFF  0o777  0b1010  1e12  2^61-1  10!  (2+3)*4
+
+Ranges:
+  START END               both included
+  a..b                    b excluded
+  a..=b                   b included
+  a+n                     a to a + n, both included
+
+Options:
+  -f, --format FORMAT     plain, json, ndjson, csv or tsv [default: plain]
+  -o, --output FILE       write the results to FILE instead of stdout
+  -c, --count             only count the products p * q instead of listing them
+  -s, --swap              swap a reversed range instead of rejecting it
+  -v, --verbose           report the method that found each prime factor, and
+                          how many moduli audit found weak
+  -d, --digits DIGITS     size of the factors ECM looks for in numbers above u64,
+                          15 to 40 [default: 20]
+  -h, --help              print this help
+  -V, --version           print the version";
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Command {
+    Primes { start: u64, end: u64 },
+    Semiprimes { start: u64, end: u64 },
+    Factor(Vec<Number>),
+    IsPrime(Vec<Number>),
+    // a file of moduli, or '-' for stdin
+    Audit(PathBuf),
+    BatchGcd(PathBuf),
+    // no command given, ask on stdin
+    Prompt,
+    Help,
+    Version,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Cli {
+    pub command: Command,
+    pub format: Format,
+    pub output: Option<PathBuf>,
+    pub count: bool,
+    pub swap: bool,
+    pub verbose: bool,
+    pub digits: u32,
+    // notes on ranges that were swapped or cannot hold any results
+    pub warnings: Vec<String>,
+}
+
+// options may appear anywhere, the first positional argument selects the command
+pub fn parse_args(args: &[String]) -> Result<Cli, FactorError> {
+    let mut format = Format::default();
+    let mut output = None;
+    let mut count = false;
+    let mut swap = false;
+    let mut verbose = false;
+    let mut digits = DEFAULT_DIGITS;
+    let mut warnings = Vec::new();
+    let mut positional = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Cli::new(Command::Help)),
+            "-V" | "--version" => return Ok(Cli::new(Command::Version)),
+            "-c" | "--count" => count = true,
+            "-s" | "--swap" => swap = true,
+            "-v" | "--verbose" => verbose = true,
+            "-f" | "--format" => {
+                format = value(arg, args.next())?.parse()?;
+            }
+            "-d" | "--digits" => {
+                let value = value(arg, args.next())?;
+                digits =
+                    u32::try_from(parse_number(value)?).map_err(|_| FactorError::Overflow {
+                        token: value.to_string(),
+                        span: None,
+                    })?;
+                if !DIGITS.contains(&digits) {
+                    return Err(FactorError::Usage(format!(
+                        "'{arg}' takes {} to {} digits, not {digits}",
+                        DIGITS.start(),
+                        DIGITS.end()
+                    )));
+                }
+            }
+            "-o" | "--output" => {
+                output = Some(PathBuf::from(value(arg, args.next())?));
+            }
+            _ if arg.starts_with('-') && arg.len() > 1 => {
+                return Err(FactorError::Usage(format!("unknown option '{arg}'")));
+            }
+            _ => positional.push(arg.as_str()),
+        }
+    }
+
+    let command = match positional.split_first() {
+        None => Command::Prompt,
+        Some((&"primes", rest)) => {
+            let (start, end) = range("primes", rest)?;
+            let (start, end) =
+                checked_range("primes", start, end, MAX_PRIMES_RANGE, swap, &mut warnings)?;
+            Command::Primes { start, end }
+        }
+        Some((&"semiprimes", rest)) => {
+            let (start, end) = range("semiprimes", rest)?;
+            let limit = semiprimes_limit(count);
+            let (start, end) = checked_range("semiprimes", start, end, limit, swap, &mut warnings)?;
+            Command::Semiprimes { start, end }
+        }
+        Some((&"factor", rest)) => Command::Factor(numbers("factor", rest)?),
+        Some((&"is-prime", rest)) => Command::IsPrime(numbers("is-prime", rest)?),
+        Some((&"audit", rest)) => Command::Audit(moduli_file("audit", rest)?),
+        Some((&"batch-gcd", rest)) => Command::BatchGcd(moduli_file("batch-gcd", rest)?),
+        Some((other, _)) => {
+            return Err(FactorError::Usage(format!("unknown command '{other}'")));
+        }
+    };
+
+    Ok(Cli {
+        command,
+        format,
+        output,
+        count,
+        swap,
+        verbose,
+        digits,
+        warnings,
+    })
+}
+
+impl Cli {
+    fn new(command: Command) -> Self {
+        Cli {
+            command,
+            format: Format::default(),
+            output: None,
+            count: false,
+            swap: false,
+            verbose: false,
+            digits: DEFAULT_DIGITS,
+            warnings: Vec::new(),
+        }
+    }
+}
+
+fn value<'a>(flag: &str, value: Option<&'a String>) -> Result<&'a str, FactorError> {
+    value
+        .map(String::as_str)
+        .ok_or_else(|| FactorError::Usage(format!("'{flag}' needs a value")))
+}
+
+fn range(command: &str, args: &[&str]) -> Result<(u64, u64), FactorError> {
+    match args {
+        [range] => expr::parse_range(range),
+        [start, end] => Ok((parse_number(start)?, parse_number(end)?)),
+        _ => Err(FactorError::WrongArity {
+            command: command.to_string(),
+            expected: "a range: 'start end', 'a..b', 'a..=b' or 'a+n'",
+            found: args.len(),
+        }),
+    }
+}
+
+fn numbers(command: &str, args: &[&str]) -> Result<Vec<Number>, FactorError> {
+    if args.is_empty() {
+        return Err(FactorError::WrongArity {
+            command: command.to_string(),
+            expected: "at least 1 number",
+            found: 0,
+        });
+    }
+    args.iter().map(|arg| parse_wide(arg)).collect()
+}
+
+fn moduli_file(command: &str, args: &[&str]) -> Result<PathBuf, FactorError> {
+    match args {
+        [path] => Ok(PathBuf::from(path)),
+        _ => Err(FactorError::WrongArity {
+            command: command.to_string(),
+            expected: "a file of moduli, one per line",
+            found: args.len(),
+        }),
+    }
+}
+
+// counting keeps only the prime list, listing also merges the pairs
+pub fn semiprimes_limit(count: bool) -> u64 {
+    if count {
+        MAX_PRIMES_RANGE
+    } else {
+        MAX_SEMIPRIMES_RANGE
+    }
+}
+
+// reject ranges that need more than limit numbers, and reversed ones unless swap is set,
+// ranges that cannot hold a result are kept but noted in warnings
+pub fn checked_range(
+    mode: &'static str,
+    start: u64,
+    end: u64,
+    limit: u64,
+    swap: bool,
+    warnings: &mut Vec<String>,
+) -> Result<(u64, u64), FactorError> {
+    let (start, end) = match (start > end, swap) {
+        (false, _) => (start, end),
+        (true, false) => return Err(FactorError::StartAfterEnd { start, end }),
+        (true, true) => {
+            warnings.push(format!(
+                "start {start} is greater than end {end}, using {end}..={start}"
+            ));
+            (end, start)
+        }
+    };
+    // end - start + 1 would overflow for 0..=u64::MAX
+    if end - start >= limit {
+        return Err(FactorError::RangeTooLarge {
+            mode,
+            start,
+            end,
+            limit,
+        });
+    }
+
+    // the smallest prime is 2 and the smallest semiprime 2 * 3
+    let min_end = if mode == "semiprimes" { 3 } else { 2 };
+    if end < min_end {
+        warnings.push(format!("range {start}..={end} holds no {mode}"));
+    } else if start == end {
+        warnings.push(format!("range {start}..={end} holds a single number"));
+    }
+    Ok((start, end))
+}
+
+// one number per line, blank lines and lines starting with '#' are skipped
+pub fn parse_lines(text: &str) -> Result<Vec<Number>, FactorError> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(parse_wide)
+        .collect()
+}
+
+pub fn parse_number(token: &str) -> Result<u64, FactorError> {
+    expr::parse_number(token)
+}
+
+// a number of any size, in the narrowest type that holds it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Number {
+    U64(u64),
+    U128(u128),
+    Big(BigUint),
+}
+
+// numbers above u64 are evaluated again without the limit
+pub fn parse_wide(token: &str) -> Result<Number, FactorError> {
+    match expr::parse_number(token) {
+        Ok(n) => Ok(Number::U64(n)),
+        Err(FactorError::Overflow { .. }) => {
+            // an intermediate result may overflow while the number itself fits, as in 2^64-1
+            let n = expr::parse_big(token)?;
+            Ok(match (n.to_u64(), n.to_u128()) {
+                (Some(n), _) => Number::U64(n),
+                (None, Some(n)) => Number::U128(n),
+                (None, None) => Number::Big(n),
+            })
+        }
+        Err(err) => Err(err),
+    }
+}
+
+impl Number {
+    pub fn prime(&self) -> bool {
+        match self {
+            Number::U64(n) => n.prime(),
+            Number::U128(n) => n.prime(),
+            Number::Big(n) => n.prime(),
+        }
+    }
+
+    pub fn to_big(&self) -> BigUint {
+        match self {
+            Number::U64(n) => BigUint::from(*n),
+            Number::U128(n) => BigUint::from(*n),
+            Number::Big(n) => n.clone(),
+        }
+    }
+
+    // with the method that found each prime, the size-based dispatch of Pipeline::new
+    // is fastest for u64
+    pub fn factor(&self, pipeline: &Pipeline) -> Vec<(Number, u32, Method)> {
+        match self {
+            Number::U64(n) => n
+                .factor_with(&Pipeline::new())
+                .into_iter()
+                .map(|(p, exp, method)| (Number::U64(p), exp, method))
+                .collect(),
+            Number::U128(n) => n
+                .factor_with(pipeline)
+                .into_iter()
+                .map(|(p, exp, method)| (Number::U128(p), exp, method))
+                .collect(),
+            Number::Big(n) => n
+                .clone()
+                .factor_with(pipeline)
+                .into_iter()
+                .map(|(p, exp, method)| (Number::Big(p), exp, method))
+                .collect(),
+        }
+    }
+}
+
+impl fmt::Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Number::U64(n) => write!(f, "{n}"),
+            Number::U128(n) => write!(f, "{n}"),
+            Number::Big(n) => write!(f, "{n}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Result<Cli, FactorError> {
+        let args: Vec<String> = args.split_whitespace().map(String::from).collect();
+        parse_args(&args)
+    }
+
+    #[test]
+    fn parsed_commands() {
+        assert_eq!(
+            Command::Primes { start: 1, end: 100 },
+            parse("primes 1 100").unwrap().command
+        );
+        assert_eq!(
+            Command::Semiprimes { start: 5, end: 9 },
+            parse("semiprimes 5 9").unwrap().command
+        );
+        assert_eq!(
+            Command::Factor(vec![Number::U64(360), Number::U64(7)]),
+            parse("factor 360 7").unwrap().command
+        );
+        assert_eq!(
+            Command::IsPrime(vec![Number::U64(97)]),
+            parse("is-prime 97").unwrap().command
+        );
+        assert_eq!(
+            Command::Audit(PathBuf::from("keys.txt")),
+            parse("audit keys.txt").unwrap().command
+        );
+        assert_eq!(
+            Command::BatchGcd(PathBuf::from("-")),
+            parse("batch-gcd -").unwrap().command
+        );
+        assert_eq!(Command::Prompt, parse("").unwrap().command);
+        assert_eq!(Command::Help, parse("factor 1 --help").unwrap().command);
+        assert_eq!(Command::Version, parse("-V").unwrap().command);
+    }
+
+    #[test]
+    fn parsed_options() {
+        let cli = parse("-f csv semiprimes 1 10 --count -o out.csv").unwrap();
+        assert_eq!(Format::Csv, cli.format);
+        assert_eq!(Some(PathBuf::from("out.csv")), cli.output);
+        assert!(cli.count);
+
+        assert!(parse("factor 10 -v").unwrap().verbose);
+        assert_eq!(30, parse("factor 10 --digits 30").unwrap().digits);
+        assert_eq!(DEFAULT_DIGITS, parse("factor 10").unwrap().digits);
+        assert!(matches!(
+            parse("factor 10 -d 2^32"),
+            Err(FactorError::Overflow { .. })
+        ));
+        for digits in ["14", "41", "0"] {
+            assert!(matches!(
+                parse(&format!("factor 10 -d {digits}")),
+                Err(FactorError::Usage(_))
+            ));
+        }
+        assert_eq!(15, parse("factor 10 -d 15").unwrap().digits);
+        assert_eq!(40, parse("factor 10 -d 40").unwrap().digits);
+        assert!(!parse("factor 10").unwrap().verbose);
+
+        let cli = parse("--format json").unwrap();
+        assert_eq!(Command::Prompt, cli.command);
+        assert_eq!(Format::Json, cli.format);
+    }
+
+    #[test]
+    fn usage_errors() {
+        assert!(matches!(parse("divide 1 2"), Err(FactorError::Usage(_))));
+        assert!(matches!(
+            parse("factor 1 --frob"),
+            Err(FactorError::Usage(_))
+        ));
+        assert!(matches!(
+            parse("factor 1 --format"),
+            Err(FactorError::Usage(_))
+        ));
+    }
+
+    #[test]
+    fn parsed_expressions() {
+        assert_eq!(
+            Command::Primes {
+                start: 1_000_000_000_000,
+                end: 1_000_000_010_000
+            },
+            parse("primes 1e12+10000").unwrap().command
+        );
+        assert_eq!(
+            Command::Semiprimes { start: 10, end: 19 },
+            parse("semiprimes 10..20").unwrap().command
+        );
+        assert_eq!(
+            Command::Primes {
+                start: 0xFFFF,
+                end: 2u64.pow(20)
+            },
+            parse("primes 0xFFFF 2^20").unwrap().command
+        );
+        assert_eq!(
+            Command::Factor(vec![
+                Number::U64(2_305_843_009_213_693_951),
+                Number::U64(3_628_800),
+                Number::U64(1_000_000)
+            ]),
+            parse("factor 2^61-1 10! 1_000_000").unwrap().command
+        );
+    }
+
+    #[test]
+    fn wrong_arity() {
+        assert!(matches!(
+            parse("primes"),
+            Err(FactorError::WrongArity { found: 0, .. })
+        ));
+        assert!(matches!(
+            parse("semiprimes 1 2 3"),
+            Err(FactorError::WrongArity { found: 3, .. })
+        ));
+        assert!(matches!(
+            parse("factor"),
+            Err(FactorError::WrongArity { found: 0, .. })
+        ));
+        assert!(matches!(
+            parse("audit a.txt b.txt"),
+            Err(FactorError::WrongArity { found: 2, .. })
+        ));
+        assert!(matches!(
+            parse("batch-gcd"),
+            Err(FactorError::WrongArity { found: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn parsed_lines() {
+        assert_eq!(
+            vec![Number::U64(15), Number::U128(1 << 64), Number::U64(77)],
+            parse_lines("# moduli\n15\n\n  2^64  \n77").unwrap()
+        );
+        assert!(parse_lines("").unwrap().is_empty());
+        assert!(matches!(
+            parse_lines("15\nkey"),
+            Err(FactorError::Parse { token, .. }) if token == "key"
+        ));
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(matches!(
+            parse("factor 12 x"),
+            Err(FactorError::Parse { token, .. }) if token == "x"
+        ));
+        assert!(matches!(
+            parse("factor 1 --format yaml"),
+            Err(FactorError::Parse { token, .. }) if token == "yaml"
+        ));
+        assert!(matches!(
+            parse("is-prime 1.5"),
+            Err(FactorError::Parse { span: Some(span), .. }) if span == (1..2)
+        ));
+        assert!(matches!(parse("primes 1"), Err(FactorError::Parse { .. })));
+        // a leading '-' is taken for an option
+        assert!(matches!(parse("is-prime -5"), Err(FactorError::Usage(_))));
+    }
+
+    #[test]
+    fn wide_numbers() {
+        assert_eq!(
+            Command::Factor(vec![
+                Number::U64(u64::MAX),
+                Number::U128(1 << 64),
+                Number::U128(u128::MAX),
+                Number::Big("340282366920938463463374607431768211456".parse().unwrap())
+            ]),
+            parse("factor 2^64-1 18446744073709551616 2^128-1 2^128")
+                .unwrap()
+                .command
+        );
+        let Command::IsPrime(numbers) = parse("is-prime 2^127-1 2^521-1 2^523-1").unwrap().command
+        else {
+            panic!("expected is-prime");
+        };
+        assert_eq!(
+            vec![true, true, false],
+            numbers.iter().map(Number::prime).collect::<Vec<_>>()
+        );
+        let n = parse_wide("2^192-1").unwrap();
+        let factors = n.factor(&Pipeline::default());
+        assert_eq!(15, factors.len());
+        assert_eq!(
+            (Number::Big(BigUint::from(3u64)), 2, Method::TrialDivision),
+            factors[0]
+        );
+        assert_eq!(
+            "6277101735386680763835789423207666416102355444464034512895",
+            n.to_string()
+        );
+        assert!(matches!(
+            parse("factor 2^65536"),
+            Err(FactorError::Parse { .. })
+        ));
+        assert!(matches!(
+            parse("factor 1-2"),
+            Err(FactorError::Overflow { .. })
+        ));
+    }
+
+    #[test]
+    fn overflow() {
+        assert!(matches!(
+            parse("primes 0 18446744073709551616"),
+            Err(FactorError::Overflow { token, .. }) if token == "18446744073709551616"
+        ));
+        assert_eq!(
+            Ok(u64::MAX),
+            parse_number("18446744073709551615").map_err(|_| ())
+        );
+    }
+
+    #[test]
+    fn start_after_end() {
+        assert!(matches!(
+            parse("primes 10 1"),
+            Err(FactorError::StartAfterEnd { start: 10, end: 1 })
+        ));
+        assert!(matches!(
+            parse("primes 10..=1"),
+            Err(FactorError::StartAfterEnd { start: 10, end: 1 })
+        ));
+        assert!(matches!(
+            parse("primes 18446744073709551615 0"),
+            Err(FactorError::StartAfterEnd {
+                start: u64::MAX,
+                end: 0
+            })
+        ));
+    }
+
+    #[test]
+    fn swapped_range() {
+        let cli = parse("primes 100 10 --swap").unwrap();
+        assert_eq!(
+            Command::Primes {
+                start: 10,
+                end: 100
+            },
+            cli.command
+        );
+        assert_eq!(
+            vec!["start 100 is greater than end 10, using 10..=100"],
+            cli.warnings
+        );
+
+        let cli = parse("-s semiprimes 20..=10").unwrap();
+        assert_eq!(Command::Semiprimes { start: 10, end: 20 }, cli.command);
+        assert_eq!(1, cli.warnings.len());
+
+        // ordered ranges are left alone
+        let cli = parse("primes 10 100 --swap").unwrap();
+        assert_eq!(
+            Command::Primes {
+                start: 10,
+                end: 100
+            },
+            cli.command
+        );
+        assert!(cli.warnings.is_empty());
+    }
+
+    #[test]
+    fn degenerate_ranges() {
+        let warnings = |args| parse(args).unwrap().warnings;
+        assert_eq!(vec!["range 0..=0 holds no primes"], warnings("primes 0 0"));
+        assert_eq!(vec!["range 0..=1 holds no primes"], warnings("primes 0 1"));
+        assert!(warnings("primes 0 2").is_empty());
+        assert_eq!(
+            vec!["range 2..=2 holds a single number"],
+            warnings("primes 2 2")
+        );
+        assert_eq!(
+            vec!["range 0..=2 holds no semiprimes"],
+            warnings("semiprimes 0..3")
+        );
+        assert_eq!(
+            vec!["range 18446744073709551615..=18446744073709551615 holds a single number"],
+            warnings("primes 0xffff_ffff_ffff_ffff 0xffff_ffff_ffff_ffff")
+        );
+        assert_eq!(
+            vec![
+                "start 1 is greater than end 0, using 0..=1",
+                "range 0..=1 holds no primes"
+            ],
+            warnings("primes 1 0 --swap")
+        );
+        // a..a is empty, not a single number
+        assert!(matches!(
+            parse("primes 2..2"),
+            Err(FactorError::Parse { .. })
+        ));
+    }
+
+    #[test]
+    fn range_too_large() {
+        assert!(matches!(
+            parse("primes 0 18446744073709551615"),
+            Err(FactorError::RangeTooLarge { mode: "primes", .. })
+        ));
+        let end = (MAX_SEMIPRIMES_RANGE + 1).to_string();
+        assert!(matches!(
+            parse(&format!("semiprimes 1 {end}")),
+            Err(FactorError::RangeTooLarge {
+                mode: "semiprimes",
+                ..
+            })
+        ));
+        assert!(parse("primes 0 18446744073709551615 --swap").is_err());
+        let start = u64::MAX - MAX_PRIMES_RANGE + 1;
+        assert!(parse(&format!("primes {start} 18446744073709551615")).is_ok());
+        // counting does not list the pairs, so it allows larger ranges
+        assert!(parse(&format!("semiprimes 1 {end} --count")).is_ok());
+        assert!(parse(&format!("semiprimes 2 {end}")).is_ok());
+    }
+}
@@ -0,0 +1,310 @@
+//! Lenstra's elliptic curve method on Montgomery curves `B y^2 = x^3 + A x^2 + x`.
+//!
+//! Each curve is a new chance at a prime factor `p`: it succeeds when the order of
+//! the curve modulo `p`, which lies within `p + 1 ± 2√p`, has only small prime
+//! factors. Suyama's parametrization makes every order divisible by 12. Points only
+//! keep `x` and `z` in projective coordinates, so no step needs an inverse.
+
+use crate::{collect_primes, ring::Residues, smooth::prime_power};
+use rayon::prelude::*;
+
+// suyama's sigma must avoid 0, ±1, ±3, ±5 and ±5/3, the curves count up from here
+const FIRST_SIGMA: i64 = 6;
+
+// giant steps in stage 2 are multiples of the wheel, baby steps the odd numbers below
+// half of it that are coprime to it, φ(2310) / 2 = 240 of them
+const WHEEL: u64 = 2310;
+const BABY_STEPS: usize = 240;
+
+// the position of the odd j among the baby steps at j / 2, u16::MAX where j shares a
+// factor with the wheel
+const BABY_INDEX: [u16; (WHEEL / 4 + 1) as usize] = {
+    let mut index = [u16::MAX; (WHEEL / 4 + 1) as usize];
+    let mut next = 0;
+    let mut j = 1;
+    while j < WHEEL / 2 {
+        if j % 3 != 0 && j % 5 != 0 && j % 7 != 0 && j % 11 != 0 {
+            index[(j / 2) as usize] = next;
+            next += 1;
+        }
+        j += 2;
+    }
+    assert!(next as usize == BABY_STEPS);
+    index
+};
+
+// primes between two gcds in stage 2
+const STAGE_TWO_BATCH: usize = 1024;
+
+// runs the curves in parallel, returns a divisor of n that is neither 1 nor n from the
+// first curve that finds one
+pub(crate) fn ecm<R>(ring: &R, b1: u64, b2: u64, curves: u32) -> Option<R::T>
+where
+    R: Residues + Sync,
+    R::T: Send + Sync,
+{
+    let stage_one = collect_primes(0, b1);
+    // every prime in stage 2 has to be coprime to the wheel
+    let stage_two = collect_primes(b1.max(11) + 1, b2);
+
+    (0..curves).into_par_iter().find_map_any(|i| {
+        let curve = Curve::suyama(ring, FIRST_SIGMA + i as i64);
+        let point = curve.stage_one(&stage_one, b1)?;
+        curve.stage_two(&point, &stage_two)
+    })
+}
+
+#[derive(Clone)]
+struct Point<T> {
+    x: T,
+    z: T,
+}
+
+// a curve with (A + 2) / 4 = a24 / d, and its starting point
+struct Curve<'a, R: Residues> {
+    ring: &'a R,
+    a24: R::T,
+    d: R::T,
+    start: Point<R::T>,
+}
+
+impl<'a, R: Residues> Curve<'a, R> {
+    // u = sigma^2 - 5, v = 4 sigma, the point (u^3 : v^3) and
+    // (A + 2) / 4 = (v - u)^3 (3u + v) / (16 u^3 v)
+    fn suyama(ring: &'a R, sigma: i64) -> Self {
+        let u = ring.residue(sigma * sigma - 5);
+        let v = ring.residue(4 * sigma);
+        let cube = |a: &R::T| ring.mul(&ring.mul(a, a), a);
+        let (u3, v3) = (cube(&u), cube(&v));
+
+        let three_u = ring.add(&ring.add(&u, &u), &u);
+        let a24 = ring.mul(&cube(&ring.sub(&v, &u)), &ring.add(&three_u, &v));
+        let d = ring.mul(&ring.mul(&ring.residue(16), &u3), &v);
+        Curve {
+            ring,
+            a24,
+            d,
+            start: Point { x: u3, z: v3 },
+        }
+    }
+
+    // 2P from P
+    fn double(&self, p: &Point<R::T>) -> Point<R::T> {
+        let ring = self.ring;
+        let sum = ring.add(&p.x, &p.z);
+        let diff = ring.sub(&p.x, &p.z);
+        let t1 = ring.mul(&sum, &sum);
+        let t2 = ring.mul(&diff, &diff);
+        let t3 = ring.sub(&t1, &t2);
+        // X = t1 t2 and Z = t3 (t2 + a24 t3), both multiplied by d
+        let dt2 = ring.mul(&self.d, &t2);
+        Point {
+            x: ring.mul(&dt2, &t1),
+            z: ring.mul(&t3, &ring.add(&dt2, &ring.mul(&self.a24, &t3))),
+        }
+    }
+
+    // P + Q from P, Q and P - Q
+    fn add(&self, p: &Point<R::T>, q: &Point<R::T>, diff: &Point<R::T>) -> Point<R::T> {
+        let ring = self.ring;
+        let u = ring.mul(&ring.sub(&p.x, &p.z), &ring.add(&q.x, &q.z));
+        let v = ring.mul(&ring.add(&p.x, &p.z), &ring.sub(&q.x, &q.z));
+        let sum = ring.add(&u, &v);
+        let dif = ring.sub(&u, &v);
+        Point {
+            x: ring.mul(&diff.z, &ring.mul(&sum, &sum)),
+            z: ring.mul(&diff.x, &ring.mul(&dif, &dif)),
+        }
+    }
+
+    // kP for k >= 1 by montgomery's ladder on (jP, (j + 1)P)
+    fn multiply(&self, p: &Point<R::T>, k: u64) -> Point<R::T> {
+        let (mut low, mut high) = (p.clone(), self.double(p));
+        for i in (0..63 - k.leading_zeros()).rev() {
+            if k >> i & 1 == 1 {
+                low = self.add(&high, &low, p);
+                high = self.double(&high);
+            } else {
+                high = self.add(&high, &low, p);
+                low = self.double(&low);
+            }
+        }
+        low
+    }
+
+    // the start point times every prime power up to b1, None if every factor of n turned
+    // up at once
+    fn stage_one(&self, primes: &[u64], b1: u64) -> Option<Point<R::T>> {
+        let mut point = self.start.clone();
+        for &p in primes {
+            point = self.multiply(&point, prime_power(p, b1));
+        }
+        if point.z == self.ring.residue(0) {
+            return None;
+        }
+        Some(point)
+    }
+
+    // baby-step giant-step: q = kW ± j has q Q = O mod p exactly when kW Q and j Q have
+    // the same x modulo p, so the cross products X_kW Z_j - X_j Z_kW collect the factor
+    fn stage_two(&self, q: &Point<R::T>, primes: &[u64]) -> Option<R::T> {
+        let ring = self.ring;
+        if let Some(d) = ring.divisor(&q.z) {
+            return Some(d);
+        }
+        let &first = primes.first()?;
+        let half = WHEEL / 2;
+
+        // j Q for the baby steps j, walking every odd j from (j + 2) Q = j Q + 2 Q with
+        // difference (j - 2) Q but keeping only those coprime to the wheel
+        let q2 = self.double(q);
+        let mut baby = Vec::with_capacity(BABY_STEPS);
+        let (mut before, mut last) = (q.clone(), self.add(&q2, q, q));
+        baby.push(q.clone());
+        for (i, &index) in BABY_INDEX.iter().enumerate().skip(1) {
+            if i > 1 {
+                let next = self.add(&last, &q2, &before);
+                before = std::mem::replace(&mut last, next);
+            }
+            if index != u16::MAX {
+                baby.push(last.clone());
+            }
+        }
+        // a prime of stage 2 is coprime to the wheel, so is its distance to kW
+        let baby_step = |j: u64| &baby[BABY_INDEX[(j / 2) as usize] as usize];
+
+        // giant steps kW Q and (k + 1) W Q, advanced by adding W Q
+        let giant = self.multiply(q, WHEEL);
+        let mut k = ((first + half) / WHEEL).max(1);
+        let mut low = self.multiply(q, k * WHEEL);
+        let mut high = self.multiply(q, (k + 1) * WHEEL);
+
+        let zero = ring.residue(0);
+        let mut terms = Vec::with_capacity(STAGE_TWO_BATCH);
+        for batch in primes.chunks(STAGE_TWO_BATCH) {
+            terms.clear();
+            for &p in batch {
+                let nearest = (p + half) / WHEEL;
+                if nearest == 0 {
+                    // p below half the wheel is a baby step itself
+                    terms.push(baby_step(p).z.clone());
+                    continue;
+                }
+                while k < nearest {
+                    let next = self.add(&high, &giant, &low);
+                    low = std::mem::replace(&mut high, next);
+                    k += 1;
+                }
+                let j = baby_step(p.abs_diff(k * WHEEL));
+                terms.push(ring.sub(&ring.mul(&low.x, &j.z), &ring.mul(&j.x, &low.z)));
+            }
+
+            let acc = terms
+                .iter()
+                .fold(ring.residue(1), |acc, term| ring.mul(&acc, term));
+            if acc == zero {
+                // the factors of n turned up for different primes of this batch
+                return terms.iter().find_map(|term| ring.divisor(term));
+            }
+            if let Some(d) = ring.divisor(&acc) {
+                return Some(d);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        bigint::BigUint,
+        montgomery::{BigMontgomery, Montgomery},
+    };
+
+    #[test]
+    fn curve_arithmetic() {
+        let ring = Montgomery::new(1_000_003);
+        let curve = Curve::suyama(&ring, 7);
+        let p = curve.start.clone();
+        // x of kP is the same whichever way it is computed, compared as x/z
+        let same = |a: &Point<u128>, b: &Point<u128>| {
+            Residues::mul(&ring, &a.x, &b.z) == Residues::mul(&ring, &b.x, &a.z)
+        };
+        let p2 = curve.double(&p);
+        let p3 = curve.add(&p2, &p, &p);
+        let p5 = curve.add(&p3, &p2, &p);
+        assert!(same(&p2, &curve.multiply(&p, 2)));
+        assert!(same(&p3, &curve.multiply(&p, 3)));
+        assert!(same(&p5, &curve.multiply(&p, 5)));
+        assert!(same(&curve.double(&p5), &curve.multiply(&p, 10)));
+        assert!(same(
+            &curve.multiply(&curve.multiply(&p, 12), 35),
+            &curve.multiply(&p, 420)
+        ));
+    }
+
+    #[test]
+    fn baby_steps() {
+        let coprime: Vec<u64> = (1..WHEEL / 2)
+            .filter(|&j| crate::arith::gcd(j, WHEEL) == 1)
+            .collect();
+        assert_eq!(BABY_STEPS, coprime.len());
+        for (i, &j) in coprime.iter().enumerate() {
+            assert_eq!(i as u16, BABY_INDEX[(j / 2) as usize], "{j}");
+        }
+        let shared = (1..WHEEL / 2)
+            .step_by(2)
+            .filter(|&j| crate::arith::gcd(j, WHEEL) != 1);
+        assert!(
+            shared
+                .into_iter()
+                .all(|j| BABY_INDEX[(j / 2) as usize] == u16::MAX)
+        );
+    }
+
+    #[test]
+    fn stage_two_matches_naive() {
+        let n: u128 = 1_000_000_007 * 998_244_353;
+        let ring = Montgomery::new(n);
+        let stage_one = collect_primes(0, 100);
+        let stage_two = collect_primes(101, 20_000);
+        let mut found = 0;
+        for sigma in FIRST_SIGMA..FIRST_SIGMA + 30 {
+            let curve = Curve::suyama(&ring, sigma);
+            let Some(point) = curve.stage_one(&stage_one, 100) else {
+                continue;
+            };
+            // q Q for every prime q on its own
+            let naive = stage_two.iter().find_map(|&q| {
+                let z = curve.multiply(&point, q).z;
+                ring.divisor(&z)
+            });
+            if naive.is_some() {
+                found += 1;
+                assert!(
+                    curve.stage_two(&point, &stage_two).is_some(),
+                    "sigma {sigma}"
+                );
+            }
+        }
+        assert!(found > 0);
+    }
+
+    #[test]
+    fn finds_factors() {
+        // two 16 digit primes, far beyond what rho does in a test
+        let (p, q) = (1_000_000_000_000_037u128, 1_000_000_000_000_091u128);
+        let ring = Montgomery::new(p * q);
+        let d = ecm(&ring, 2_000, 200_000, 200).unwrap();
+        assert!(d == p || d == q);
+
+        // a 12 digit factor of a 190 bit number
+        let big = &(&(&BigUint::one() << 127) - &BigUint::one())
+            * &BigUint::from(10_000_000_000_000_061u128);
+        let n = &big * &BigUint::from(100_000_000_003u64);
+        let ring = BigMontgomery::new(&n);
+        let d = ecm(&ring, 2_000, 200_000, 200).unwrap();
+        assert!((&n % &d).is_zero() && !d.is_one() && d != n);
+    }
+}
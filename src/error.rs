@@ -0,0 +1,217 @@
+//! The error type shared by parsing and output.
+
+use std::{error::Error, fmt, io, ops::Range};
+
+/// Everything that can go wrong between reading the input and writing the results.
+#[derive(Debug)]
+pub enum FactorError {
+    /// Unknown command or option, or an option without its value.
+    Usage(String),
+    /// A command got the wrong number of inputs.
+    WrongArity {
+        /// The command that was given.
+        command: String,
+        /// What the command expects, e.g. "2 inputs: 'start' and 'end'".
+        expected: &'static str,
+        /// How many inputs it got.
+        found: usize,
+    },
+    /// A token that could not be parsed.
+    Parse {
+        /// The offending token.
+        token: String,
+        /// Why it was rejected.
+        reason: String,
+        /// Byte range of the offending part within the token, if known.
+        span: Option<Range<usize>>,
+    },
+    /// A number that does not fit into `u64`.
+    Overflow {
+        /// The offending token.
+        token: String,
+        /// Byte range of the overflowing part within the token, if known.
+        span: Option<Range<usize>>,
+    },
+    /// A range whose start is greater than its end.
+    StartAfterEnd {
+        /// Start of the range.
+        start: u64,
+        /// End of the range.
+        end: u64,
+    },
+    /// The range needs more memory than the mode allows.
+    RangeTooLarge {
+        /// The mode the range was given to.
+        mode: &'static str,
+        /// Start of the range.
+        start: u64,
+        /// End of the range.
+        end: u64,
+        /// Largest supported number of elements.
+        limit: u64,
+    },
+    /// Reading the input or writing the results failed.
+    Io(io::Error),
+}
+
+impl fmt::Display for FactorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FactorError::Usage(msg) => write!(f, "{msg}"),
+            FactorError::WrongArity {
+                command,
+                expected,
+                found,
+            } => write!(f, "'{command}' needs {expected}, got {found} inputs"),
+            FactorError::Parse {
+                token,
+                reason,
+                span,
+            } => {
+                write!(f, "invalid input '{token}': {reason}")?;
+                write_span(f, token, span)
+            }
+            FactorError::Overflow { token, span } => {
+                write!(f, "'{token}' does not fit into u64 (max {})", u64::MAX)?;
+                write_span(f, token, span)
+            }
+            FactorError::StartAfterEnd { start, end } => {
+                write!(f, "start {start} is greater than end {end}")
+            }
+            FactorError::RangeTooLarge {
+                mode,
+                start,
+                end,
+                limit,
+            } => write!(
+                f,
+                "range {start}..={end} is too large for '{mode}', at most {limit} numbers are supported"
+            ),
+            FactorError::Io(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+// underline the span below the token
+//   2^^3
+//     ^
+fn write_span(f: &mut fmt::Formatter<'_>, token: &str, span: &Option<Range<usize>>) -> fmt::Result {
+    let Some(span) = span else {
+        return Ok(());
+    };
+    let indent = token[..span.start].chars().count();
+    let width = token[span.clone()].chars().count().max(1);
+    write!(
+        f,
+        "\n  {token}\n  {}{}",
+        " ".repeat(indent),
+        "^".repeat(width)
+    )
+}
+
+impl Error for FactorError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            FactorError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for FactorError {
+    fn from(err: io::Error) -> Self {
+        FactorError::Io(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn displayed_errors() {
+        assert_eq!(
+            "unknown command 'x'",
+            FactorError::Usage("unknown command 'x'".to_string()).to_string()
+        );
+        assert_eq!(
+            "'primes' needs 2 inputs: 'start' and 'end', got 3 inputs",
+            FactorError::WrongArity {
+                command: "primes".to_string(),
+                expected: "2 inputs: 'start' and 'end'",
+                found: 3
+            }
+            .to_string()
+        );
+        assert_eq!(
+            "invalid input '12a': invalid digit found in string",
+            FactorError::Parse {
+                token: "12a".to_string(),
+                reason: "invalid digit found in string".to_string(),
+                span: None
+            }
+            .to_string()
+        );
+        assert_eq!(
+            "'18446744073709551616' does not fit into u64 (max 18446744073709551615)",
+            FactorError::Overflow {
+                token: "18446744073709551616".to_string(),
+                span: None
+            }
+            .to_string()
+        );
+        assert_eq!(
+            "start 10 is greater than end 1",
+            FactorError::StartAfterEnd { start: 10, end: 1 }.to_string()
+        );
+        assert_eq!(
+            "range 0..=100 is too large for 'primes', at most 50 numbers are supported",
+            FactorError::RangeTooLarge {
+                mode: "primes",
+                start: 0,
+                end: 100,
+                limit: 50
+            }
+            .to_string()
+        );
+    }
+
+    #[test]
+    fn displayed_spans() {
+        assert_eq!(
+            "invalid input '2^^3': expected a number\n  2^^3\n    ^",
+            FactorError::Parse {
+                token: "2^^3".to_string(),
+                reason: "expected a number".to_string(),
+                span: Some(2..3)
+            }
+            .to_string()
+        );
+        assert_eq!(
+            "'1 + 2^70' does not fit into u64 (max 18446744073709551615)\n  1 + 2^70\n      ^^^^",
+            FactorError::Overflow {
+                token: "1 + 2^70".to_string(),
+                span: Some(4..8)
+            }
+            .to_string()
+        );
+        // an empty span at the end still gets a marker
+        assert!(
+            FactorError::Parse {
+                token: "1+".to_string(),
+                reason: "expected a number".to_string(),
+                span: Some(2..2)
+            }
+            .to_string()
+            .ends_with("\n  1+\n    ^")
+        );
+    }
+
+    #[test]
+    fn io_error_source() {
+        let err = FactorError::from(io::Error::new(io::ErrorKind::NotFound, "missing"));
+        assert!(matches!(err, FactorError::Io(_)));
+        assert_eq!("missing", err.to_string());
+        assert!(err.source().is_some());
+    }
+}
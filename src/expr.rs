@@ -0,0 +1,648 @@
+//! Number and range expressions.
+//!
+//! Numbers are written as arithmetic expressions over `u64`, or over [`BigUint`]
+//! with [`parse_big`]:
+//!
+//! - literals: `1000000`, `1_000_000`, `0xFFFF_FFFF`, `0o777`, `0b1010`, `1e12`
+//! - operators: `+`, `-`, `*`, `^` (right associative), postfix `!` and parentheses
+//!
+//! Ranges are two numbers or a single expression written as `a..b` (end excluded),
+//! `a..=b` (end included) or `a+n` (`a` up to and including `a + n`).
+//!
+//! ```
+//! use prime_factorization::expr::{parse_number, parse_range};
+//!
+//! assert_eq!(Ok(2_305_843_009_213_693_951), parse_number("2^61-1").map_err(|_| ()));
+//! assert_eq!(Ok((10, 19)), parse_range("10..20").map_err(|_| ()));
+//! assert_eq!(Ok((1_000_000_000_000, 1_000_000_010_000)), parse_range("1e12+10000").map_err(|_| ()));
+//! ```
+
+use crate::{bigint::BigUint, error::FactorError};
+use std::ops::Range;
+
+/// Largest number of bits [`parse_big`] accepts, about 19700 decimal digits.
+pub const MAX_BITS: u64 = 1 << 16;
+
+/// Evaluates a number expression.
+pub fn parse_number(input: &str) -> Result<u64, FactorError> {
+    let mut parser = Parser::new(input);
+    let n = parser.expr()?;
+    parser.finish()?;
+    Ok(n)
+}
+
+/// Evaluates a number expression without the `u64` limit.
+///
+/// Results beyond [`MAX_BITS`] are rejected as a parse error, negative ones as
+/// [`FactorError::Overflow`] like in [`parse_number`].
+///
+/// ```
+/// use prime_factorization::expr::parse_big;
+///
+/// assert_eq!(
+///     "170141183460469231731687303715884105727",
+///     parse_big("2^127-1").unwrap().to_string()
+/// );
+/// ```
+pub fn parse_big(input: &str) -> Result<BigUint, FactorError> {
+    let mut parser = Parser::new(input);
+    let n = parser.expr()?;
+    parser.finish()?;
+    Ok(n)
+}
+
+/// Evaluates a range expression `a..b`, `a..=b` or `a+n` to its inclusive bounds.
+///
+/// The bounds are not validated, `start > end` is returned as it was written.
+pub fn parse_range(input: &str) -> Result<(u64, u64), FactorError> {
+    if let Some(i) = input.find("..") {
+        let (end, offset, inclusive) = match input[i + 2..].strip_prefix('=') {
+            Some(end) => (end, i + 3, true),
+            None => (&input[i + 2..], i + 2, false),
+        };
+        let start = Parser::new(&input[..i]).whole(input, 0)?;
+        let end_value = Parser::new(end).whole(input, offset)?;
+
+        if inclusive {
+            return Ok((start, end_value));
+        }
+        // a..b excludes b, a..a is empty and has no inclusive form
+        return match end_value.checked_sub(1) {
+            Some(end) if end_value > start => Ok((start, end)),
+            _ => Err(FactorError::Parse {
+                token: input.to_string(),
+                reason: "empty range".to_string(),
+                span: Some(0..input.len()),
+            }),
+        };
+    }
+
+    // a+n, the last top level '+' separates the start from the length
+    match last_top_level_plus(input) {
+        Some(i) => {
+            let start = Parser::new(&input[..i]).whole(input, 0)?;
+            let len = Parser::new(&input[i + 1..]).whole(input, i + 1)?;
+            let end = start
+                .checked_add(len)
+                .ok_or_else(|| FactorError::Overflow {
+                    token: input.to_string(),
+                    span: Some(0..input.len()),
+                })?;
+            Ok((start, end))
+        }
+        None => Err(FactorError::Parse {
+            token: input.to_string(),
+            reason: "expected a range 'a..b', 'a..=b' or 'a+n'".to_string(),
+            span: Some(0..input.len()),
+        }),
+    }
+}
+
+/// Whether the input is written as a single range expression rather than a number.
+pub fn is_range(input: &str) -> bool {
+    input.contains("..")
+}
+
+// the arithmetic the parser needs, with None for results out of range
+trait Value: Sized {
+    fn from_u64(n: u64) -> Self;
+    fn from_digits(digits: &str, radix: u32) -> Option<Self>;
+    fn to_u64(&self) -> Option<u64>;
+    fn checked_add(&self, rhs: &Self) -> Option<Self>;
+    fn checked_sub(&self, rhs: &Self) -> Option<Self>;
+    fn checked_mul(&self, rhs: &Self) -> Option<Self>;
+    fn checked_pow(&self, exp: u32) -> Option<Self>;
+    // the error for a result above the largest value
+    fn too_large(token: String, span: Range<usize>) -> FactorError;
+}
+
+impl Value for u64 {
+    fn from_u64(n: u64) -> Self {
+        n
+    }
+
+    fn from_digits(digits: &str, radix: u32) -> Option<Self> {
+        u64::from_str_radix(digits, radix).ok()
+    }
+
+    fn to_u64(&self) -> Option<u64> {
+        Some(*self)
+    }
+
+    fn checked_add(&self, rhs: &Self) -> Option<Self> {
+        u64::checked_add(*self, *rhs)
+    }
+
+    fn checked_sub(&self, rhs: &Self) -> Option<Self> {
+        u64::checked_sub(*self, *rhs)
+    }
+
+    fn checked_mul(&self, rhs: &Self) -> Option<Self> {
+        u64::checked_mul(*self, *rhs)
+    }
+
+    fn checked_pow(&self, exp: u32) -> Option<Self> {
+        u64::checked_pow(*self, exp)
+    }
+
+    fn too_large(token: String, span: Range<usize>) -> FactorError {
+        FactorError::Overflow {
+            token,
+            span: Some(span),
+        }
+    }
+}
+
+impl Value for BigUint {
+    fn from_u64(n: u64) -> Self {
+        BigUint::from(n)
+    }
+
+    fn from_digits(digits: &str, radix: u32) -> Option<Self> {
+        BigUint::from_str_radix(digits, radix).filter(|n| n.bits() <= MAX_BITS)
+    }
+
+    fn to_u64(&self) -> Option<u64> {
+        BigUint::to_u64(self)
+    }
+
+    fn checked_add(&self, rhs: &Self) -> Option<Self> {
+        Some(self + rhs).filter(|n| n.bits() <= MAX_BITS)
+    }
+
+    fn checked_sub(&self, rhs: &Self) -> Option<Self> {
+        BigUint::checked_sub(self, rhs)
+    }
+
+    fn checked_mul(&self, rhs: &Self) -> Option<Self> {
+        (self.bits() + rhs.bits() <= MAX_BITS + 1)
+            .then(|| self * rhs)
+            .filter(|n| n.bits() <= MAX_BITS)
+    }
+
+    fn checked_pow(&self, exp: u32) -> Option<Self> {
+        // 0 and 1 stay small for any exponent, everything else is bounded up front
+        if self.bits() <= 1 {
+            return Some(if exp == 0 {
+                BigUint::one()
+            } else {
+                self.clone()
+            });
+        }
+        (self.bits().saturating_sub(1) * exp as u64 <= MAX_BITS)
+            .then(|| self.pow(exp))
+            .filter(|n| n.bits() <= MAX_BITS)
+    }
+
+    fn too_large(token: String, span: Range<usize>) -> FactorError {
+        FactorError::Parse {
+            token,
+            reason: format!("does not fit into {MAX_BITS} bits"),
+            span: Some(span),
+        }
+    }
+}
+
+fn last_top_level_plus(input: &str) -> Option<usize> {
+    let mut depth = 0i32;
+    let mut last = None;
+    for (i, c) in input.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            '+' if depth == 0 => last = Some(i),
+            _ => {}
+        }
+    }
+    last
+}
+
+// recursive descent over the bytes of the input, spans are byte offsets into `full`
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+    // where input starts within the text shown in errors
+    offset: usize,
+    full: &'a str,
+}
+
+impl<'a> Parser<'a> {
+    fn new(input: &'a str) -> Self {
+        Parser {
+            input,
+            pos: 0,
+            offset: 0,
+            full: input,
+        }
+    }
+
+    // parse a part of a larger range expression, errors point into the whole range
+    fn whole(mut self, full: &'a str, offset: usize) -> Result<u64, FactorError> {
+        self.full = full;
+        self.offset = offset;
+        let n = self.expr()?;
+        self.finish()?;
+        Ok(n)
+    }
+
+    fn finish(&mut self) -> Result<(), FactorError> {
+        self.skip_whitespace();
+        match self.peek() {
+            None => Ok(()),
+            Some(')') => Err(self.error(self.pos..self.pos + 1, "unmatched ')'")),
+            Some(c) => Err(self.error(self.pos..self.pos + c.len_utf8(), "expected an operator")),
+        }
+    }
+
+    // expr := term (('+' | '-') term)*
+    fn expr<T: Value>(&mut self) -> Result<T, FactorError> {
+        self.skip_whitespace();
+        let start = self.pos;
+        let mut value = self.term()?;
+        loop {
+            self.skip_whitespace();
+            let op = match self.peek() {
+                Some(op @ ('+' | '-')) => op,
+                _ => return Ok(value),
+            };
+            self.pos += 1;
+            let rhs = self.term()?;
+            value = match op {
+                '+' => value
+                    .checked_add(&rhs)
+                    .ok_or_else(|| self.too_large::<T>(start..self.pos))?,
+                // a negative result is out of range for every width
+                _ => value
+                    .checked_sub(&rhs)
+                    .ok_or_else(|| self.overflow(start..self.pos))?,
+            };
+        }
+    }
+
+    // term := power ('*' power)*
+    fn term<T: Value>(&mut self) -> Result<T, FactorError> {
+        self.skip_whitespace();
+        let start = self.pos;
+        let mut value = self.power()?;
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some('*') {
+                return Ok(value);
+            }
+            self.pos += 1;
+            let rhs = self.power()?;
+            value = value
+                .checked_mul(&rhs)
+                .ok_or_else(|| self.too_large::<T>(start..self.pos))?;
+        }
+    }
+
+    // power := postfix ('^' power)?
+    fn power<T: Value>(&mut self) -> Result<T, FactorError> {
+        self.skip_whitespace();
+        let start = self.pos;
+        let base: T = self.postfix()?;
+        self.skip_whitespace();
+        if self.peek() != Some('^') {
+            return Ok(base);
+        }
+        self.pos += 1;
+        let exp: T = self.power()?;
+        exp.to_u64()
+            .and_then(|exp| u32::try_from(exp).ok())
+            .and_then(|exp| base.checked_pow(exp))
+            .ok_or_else(|| self.too_large::<T>(start..self.pos))
+    }
+
+    // postfix := atom '!'*
+    fn postfix<T: Value>(&mut self) -> Result<T, FactorError> {
+        self.skip_whitespace();
+        let start = self.pos;
+        let mut value: T = self.atom()?;
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some('!') {
+                return Ok(value);
+            }
+            self.pos += 1;
+            // every factorial beyond u64 arguments is out of range anyway
+            value = value
+                .to_u64()
+                .and_then(|n| {
+                    (2..=n).try_fold(T::from_u64(1), |acc, k| acc.checked_mul(&T::from_u64(k)))
+                })
+                .ok_or_else(|| self.too_large::<T>(start..self.pos))?;
+        }
+    }
+
+    // atom := number | '(' expr ')'
+    fn atom<T: Value>(&mut self) -> Result<T, FactorError> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('(') => {
+                let open = self.pos;
+                self.pos += 1;
+                let value = self.expr()?;
+                self.skip_whitespace();
+                if self.peek() != Some(')') {
+                    return Err(self.error(open..open + 1, "unclosed '('"));
+                }
+                self.pos += 1;
+                Ok(value)
+            }
+            Some(c) if c.is_ascii_digit() => self.number(),
+            Some(c) => Err(self.error(self.pos..self.pos + c.len_utf8(), "expected a number")),
+            None => Err(self.error(self.pos..self.pos, "expected a number")),
+        }
+    }
+
+    fn number<T: Value>(&mut self) -> Result<T, FactorError> {
+        let start = self.pos;
+        let rest = &self.input[start..];
+
+        let radix = match rest.get(..2) {
+            Some("0x" | "0X") => 16,
+            Some("0o" | "0O") => 8,
+            Some("0b" | "0B") => 2,
+            _ => 10,
+        };
+        if radix != 10 {
+            self.pos += 2;
+        }
+
+        let digits = self.digits(radix);
+        if digits.is_empty() {
+            return Err(self.error(start..self.pos, "missing digits after the prefix"));
+        }
+        let mantissa: T = self.value(&digits, radix, start..self.pos)?;
+
+        // scientific notation, only for decimals since 'e' is a hex digit
+        if radix == 10 && matches!(self.peek(), Some('e' | 'E')) {
+            self.pos += 1;
+            let exp_start = self.pos;
+            let exp_digits = self.digits(10);
+            if exp_digits.is_empty() {
+                return Err(self.error(start..self.pos, "missing exponent after 'e'"));
+            }
+            let exp: u64 = self.value(&exp_digits, 10, exp_start..self.pos)?;
+            return u32::try_from(exp)
+                .ok()
+                .and_then(|exp| T::from_u64(10).checked_pow(exp))
+                .and_then(|scale| mantissa.checked_mul(&scale))
+                .ok_or_else(|| self.too_large::<T>(start..self.pos));
+        }
+
+        Ok(mantissa)
+    }
+
+    // digits of the radix with '_' separators removed
+    fn digits(&mut self, radix: u32) -> String {
+        let mut digits = String::new();
+        while let Some(c) = self.peek() {
+            if c == '_' {
+                self.pos += 1;
+            } else if c.is_digit(radix) {
+                digits.push(c);
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+        digits
+    }
+
+    fn value<T: Value>(
+        &self,
+        digits: &str,
+        radix: u32,
+        span: Range<usize>,
+    ) -> Result<T, FactorError> {
+        T::from_digits(digits, radix).ok_or_else(|| self.too_large::<T>(span))
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.input[self.pos..].chars().next()
+    }
+
+    // any unicode whitespace, which may be longer than a byte
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.peek().filter(|c| c.is_whitespace()) {
+            self.pos += c.len_utf8();
+        }
+    }
+
+    fn shifted(&self, span: Range<usize>) -> Range<usize> {
+        span.start + self.offset..span.end + self.offset
+    }
+
+    fn error(&self, span: Range<usize>, reason: &str) -> FactorError {
+        FactorError::Parse {
+            token: self.full.to_string(),
+            reason: reason.to_string(),
+            span: Some(self.shifted(span)),
+        }
+    }
+
+    fn overflow(&self, span: Range<usize>) -> FactorError {
+        FactorError::Overflow {
+            token: self.full.to_string(),
+            span: Some(self.shifted(span)),
+        }
+    }
+
+    fn too_large<T: Value>(&self, span: Range<usize>) -> FactorError {
+        T::too_large(self.full.to_string(), self.shifted(span))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn number(input: &str) -> u64 {
+        parse_number(input).unwrap()
+    }
+
+    fn parse_span(input: &str) -> Option<Range<usize>> {
+        match parse_number(input) {
+            Err(FactorError::Parse { span, .. }) => span,
+            other => panic!("expected a parse error for '{input}', got {other:?}"),
+        }
+    }
+
+    fn overflow_span(input: &str) -> Option<Range<usize>> {
+        match parse_number(input) {
+            Err(FactorError::Overflow { span, .. }) => span,
+            other => panic!("expected an overflow for '{input}', got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn literals() {
+        assert_eq!(0, number("0"));
+        assert_eq!(1_000_000, number("1_000_000"));
+        assert_eq!(0xFFFF_FFFF, number("0xFFFF_FFFF"));
+        assert_eq!(0xabc, number("0Xabc"));
+        assert_eq!(0o777, number("0o777"));
+        assert_eq!(0b1010, number("0b1010"));
+        assert_eq!(1_000_000_000_000, number("1e12"));
+        assert_eq!(25_000, number("25E3"));
+        assert_eq!(u64::MAX, number("18446744073709551615"));
+        assert_eq!(u64::MAX, number("0xffffffffffffffff"));
+    }
+
+    #[test]
+    fn operators() {
+        assert_eq!(2_305_843_009_213_693_951, number("2^61-1"));
+        assert_eq!(3_628_800, number("10!"));
+        assert_eq!(2_432_902_008_176_640_000, number("20!"));
+        assert_eq!(720, number("3!!"));
+        assert_eq!(1, number("0!"));
+        assert_eq!(14, number("2+3*4"));
+        assert_eq!(20, number("(2+3)*4"));
+    }
+
+    #[test]
+    fn precedence() {
+        // ^ is right associative and binds tighter than *
+        assert_eq!(2u64.pow(9), number("2^3^2"));
+        assert_eq!(3 * 2u64.pow(4), number("3*2^4"));
+        // ! binds tighter than ^
+        assert_eq!(6u64.pow(2), number("3!^2"));
+        assert_eq!(1, number("10 - 4 - 5"));
+        assert_eq!(5, number(" ( 5 ) "));
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(Some(0..0), parse_span(""));
+        assert_eq!(Some(2..3), parse_span("2^^3"));
+        assert_eq!(Some(0..1), parse_span("x"));
+        assert_eq!(Some(2..3), parse_span("12x"));
+        assert_eq!(Some(0..1), parse_span("(1+2"));
+        assert_eq!(Some(3..4), parse_span("1+2)"));
+        assert_eq!(Some(0..2), parse_span("0x"));
+        assert_eq!(Some(0..2), parse_span("1e"));
+        assert_eq!(Some(2..2), parse_span("1+"));
+        assert_eq!(Some(0..1), parse_span("-5"));
+        assert_eq!(Some(1..2), parse_span("1.5"));
+    }
+
+    #[test]
+    fn non_ascii_input() {
+        // no-break space, ideographic space and em space
+        assert_eq!(3, number("1\u{a0}+2"));
+        assert_eq!(7, number("7\u{3000}"));
+        assert_eq!(12, number("\u{2003}(12\u{a0})"));
+        assert_eq!((1, 5), parse_range("1\u{a0}..=\u{3000}5").unwrap());
+
+        // the spans cover whole characters, the rendered error must not split one
+        for (input, span) in [
+            ("²", 0..2),
+            ("1+²", 2..4),
+            ("7é", 1..3),
+            ("\u{a0}7\u{3000}x", 6..7),
+            ("٣", 0..2),
+            ("1 − 2", 2..5),
+            ("2^🦀", 2..6),
+        ] {
+            assert_eq!(Some(span), parse_span(input), "{input}");
+            parse_number(input).unwrap_err().to_string();
+            parse_big(input).unwrap_err().to_string();
+        }
+    }
+
+    #[test]
+    fn overflows() {
+        assert_eq!(Some(0..20), overflow_span("18446744073709551616"));
+        assert_eq!(Some(0..4), overflow_span("2^64"));
+        assert_eq!(Some(0..3), overflow_span("21!"));
+        assert_eq!(Some(0..4), overflow_span("1e20"));
+        assert_eq!(Some(0..3), overflow_span("1-2"));
+        assert_eq!(Some(4..8), overflow_span("1 + 2^70"));
+        assert_eq!(Some(0..23), overflow_span("0x1_0000_0000_0000_0000"));
+        assert_eq!(Some(0..11), overflow_span("2^32 * 2^32"));
+    }
+
+    #[test]
+    fn big_numbers() {
+        let big = |input| parse_big(input).unwrap().to_string();
+        assert_eq!("18446744073709551616", big("2^64"));
+        assert_eq!("18446744073709551616", big("18446744073709551616"));
+        assert_eq!("340282366920938463463374607431768211455", big("2^128 - 1"));
+        assert_eq!("51090942171709440000", big("21!"));
+        assert_eq!("100000000000000000000", big("1e20"));
+        assert_eq!(
+            "340282366920938463463374607431768211456",
+            big("0x1_0000_0000_0000_0000_0000_0000_0000_0000")
+        );
+        assert_eq!("1", big("1^1000000"));
+        assert_eq!("0", big("0^1000000"));
+        assert_eq!(parse_number("2^61-1").unwrap().to_string(), big("2^61-1"));
+        assert_eq!(65_536, parse_big("2^65535").unwrap().bits());
+    }
+
+    #[test]
+    fn big_errors() {
+        let reason = |input| match parse_big(input) {
+            Err(FactorError::Parse { reason, span, .. }) => (reason, span),
+            other => panic!("expected an error for '{input}', got {other:?}"),
+        };
+        assert_eq!(
+            ("does not fit into 65536 bits".to_string(), Some(0..7)),
+            reason("2^65536")
+        );
+        assert_eq!(
+            ("does not fit into 65536 bits".to_string(), Some(4..12)),
+            reason("1 + 2^100000")
+        );
+        assert!(matches!(
+            parse_big("10000!"),
+            Err(FactorError::Parse { .. })
+        ));
+        assert!(matches!(
+            parse_big("2^(2^64)"),
+            Err(FactorError::Parse { .. })
+        ));
+        // negative results are an overflow for every width
+        assert!(matches!(
+            parse_big("1-2"),
+            Err(FactorError::Overflow { span: Some(span), .. }) if span == (0..3)
+        ));
+        assert_eq!(Some(2..3), reason("2^^3").1);
+    }
+
+    #[test]
+    fn ranges() {
+        assert_eq!((10, 19), parse_range("10..20").unwrap());
+        assert_eq!((10, 20), parse_range("10..=20").unwrap());
+        assert_eq!(
+            (0, u64::MAX),
+            parse_range("0..=0xffff_ffff_ffff_ffff").unwrap()
+        );
+        assert_eq!(
+            (1_000_000_000_000, 1_000_000_010_000),
+            parse_range("1e12+10000").unwrap()
+        );
+        assert_eq!((7, 10), parse_range("(2+5)+3").unwrap());
+        assert_eq!((5, 8), parse_range("2+3+3").unwrap());
+        assert_eq!((5, 5), parse_range("5..=5").unwrap());
+        assert_eq!((20, 10), parse_range("20..=10").unwrap());
+        assert!(is_range("1..2"));
+        assert!(!is_range("1+2"));
+    }
+
+    #[test]
+    fn range_errors() {
+        let span = |input| match parse_range(input) {
+            Err(FactorError::Parse { span, .. } | FactorError::Overflow { span, .. }) => span,
+            other => panic!("expected an error for '{input}', got {other:?}"),
+        };
+        assert_eq!(Some(0..5), span("10*20"));
+        assert_eq!(Some(0..4), span("5..5"));
+        assert_eq!(Some(4..5), span("1..=x"));
+        assert_eq!(Some(0..0), span("..10"));
+        assert_eq!(Some(0..23), span("0xffff_ffff_ffff_ffff+1"));
+        assert_eq!(Some(6..6), span("10..2^"));
+    }
+}
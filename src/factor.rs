@@ -0,0 +1,1019 @@
+//! Prime factorization of single numbers.
+//!
+//! Every number goes through trial division first. A composite cofactor is then
+//! handed to the stages of a [`Pipeline`] in order, and if none of them splits it, to
+//! the method [`find_divisor`] picks for its size: trial division, Hart's one line
+//! factoring, SQUFOF or Pollard's rho. The default pipeline ends with the quadratic
+//! sieve of [`siqs`], which splits any cofactor that is not a prime power.
+//!
+//! [`siqs`]: crate::siqs
+
+use crate::{
+    arith::{gcd, mul_mod},
+    bigint::BigUint,
+    ecm::ecm,
+    fermat::fermat,
+    hart::hart_steps,
+    montgomery::{BigMontgomery, Montgomery},
+    prime::Prime,
+    ring::Residues,
+    siqs::siqs,
+    smooth::{p_minus_one, p_plus_one},
+    squfof::squfof,
+};
+use std::fmt;
+
+// trial division handles every prime factor below this bound
+const TRIAL_LIMIT: u64 = 1 << 10;
+
+// number of steps batched into a single gcd in brent's loop
+const BATCH: u64 = 128;
+
+// the rho stage of the default pipeline, about √(10^12) steps
+const RHO_ITERATIONS: u64 = 1 << 20;
+
+// find_divisor: trial division up to this many bits, SQUFOF up to the next, rho above,
+// after a few steps of hart for every size, see benches/factoring.rs. SQUFOF loses to
+// rho by 1.4 to 2.7 times from 40 bits up, and the close factors it is quick on are
+// found by the hart steps first
+const TRIAL_BITS: u32 = 22;
+const SQUFOF_BITS: u32 = 32;
+const HART_STEPS: u64 = 16;
+
+/// Prime factorization for every unsigned width.
+///
+/// ```
+/// use prime_factorization::Factor;
+///
+/// assert_eq!(vec![(2, 3), (3, 2), (5, 1)], 360u32.factor());
+/// let n: u128 = 1_000_000_007 * 998_244_353 * 18_446_744_073_709_551_557;
+/// assert_eq!(
+///     vec![(998_244_353, 1), (1_000_000_007, 1), (18_446_744_073_709_551_557, 1)],
+///     n.factor()
+/// );
+/// ```
+pub trait Factor: Sized {
+    /// Complete prime factorization as `(prime, exponent)`, sorted by prime.
+    ///
+    /// 0 and 1 have no prime factors and give an empty list. `u32` and `u64` use
+    /// [`Pipeline::new`], which picks trial division, Hart's one line method, SQUFOF
+    /// or rho by the size of each cofactor, wider numbers the default [`Pipeline`].
+    fn factor(self) -> Vec<(Self, u32)>;
+
+    /// Complete prime factorization through the stages of `pipeline`, as
+    /// `(prime, exponent, method)` sorted by prime, with the method that found the
+    /// prime first.
+    ///
+    /// ```
+    /// use prime_factorization::{Factor, factor::{Method, Pipeline}};
+    ///
+    /// // 1_000_001_269 - 1 = 2^2 * 3^3 * 7 * 17^2 * 23 * 199
+    /// let n: u128 = 1_000_001_269 * 1_000_000_007 * 6;
+    /// assert_eq!(
+    ///     vec![
+    ///         (2, 1, Method::TrialDivision),
+    ///         (3, 1, Method::TrialDivision),
+    ///         (1_000_000_007, 1, Method::PMinusOne),
+    ///         (1_000_001_269, 1, Method::PMinusOne),
+    ///     ],
+    ///     n.factor_with(&Pipeline::default())
+    /// );
+    /// ```
+    fn factor_with(self, pipeline: &Pipeline) -> Vec<(Self, u32, Method)>;
+}
+
+/// The method that split a prime factor off its cofactor.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Method {
+    /// The number itself is prime.
+    Primality,
+    /// Division by the primes below 1024.
+    TrialDivision,
+    /// Pollard's p − 1, a [`Stage::PMinusOne`].
+    PMinusOne,
+    /// Williams' p + 1, a [`Stage::PPlusOne`].
+    PPlusOne,
+    /// Fermat's method, a [`Stage::Fermat`].
+    Fermat,
+    /// Pollard's rho with Brent's cycle detection, a [`Stage::Rho`] or the fallback.
+    Rho,
+    /// Hart's one line factoring, see [`hart`](crate::hart::hart).
+    Hart,
+    /// Shanks' square forms factorization, see [`squfof`](crate::squfof::squfof).
+    Squfof,
+    /// Lenstra's elliptic curve method, a [`Stage::Ecm`].
+    Ecm,
+    /// The self-initializing quadratic sieve, a [`Stage::Siqs`].
+    Siqs,
+}
+
+impl fmt::Display for Method {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Method::Primality => "primality test",
+            Method::TrialDivision => "trial division",
+            Method::PMinusOne => "pollard p-1",
+            Method::PPlusOne => "williams p+1",
+            Method::Fermat => "fermat",
+            Method::Rho => "pollard rho",
+            Method::Hart => "hart one line",
+            Method::Squfof => "shanks squfof",
+            Method::Ecm => "elliptic curves",
+            Method::Siqs => "quadratic sieve",
+        })
+    }
+}
+
+/// A method tried on composite cofactors before the final Pollard's rho.
+///
+/// p − 1 and p + 1 find a prime factor `p` however large it is, as long as `p − 1` or
+/// `p + 1` is a product of small prime powers. Their cost only depends on the bounds.
+/// ECM works on any `p`, with bounds that grow with its size, see [`Stage::ecm`].
+/// Fermat's method finds two factors close to each other, however large they are.
+/// The quadratic sieve does not depend on `p` at all, only on the size of the cofactor.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stage {
+    /// Pollard's p − 1, finds `p` if every prime power of `p − 1` is at most `b1`,
+    /// except for a single prime of at most `b2`.
+    PMinusOne {
+        /// Stage 1 bound.
+        b1: u64,
+        /// Stage 2 bound, no stage 2 unless it is above `b1`.
+        b2: u64,
+    },
+    /// Williams' p + 1, finds `p` if every prime power of `p + 1` is at most `b1`.
+    PPlusOne {
+        /// Stage 1 bound.
+        b1: u64,
+    },
+    /// Fermat's method, finds two factors `p < q` of `n` within about
+    /// `(√q - √p)^2 / 2` steps, see [`fermat`](crate::fermat::fermat). A single step
+    /// if `q - p` is below `2 n^(1/4)`.
+    Fermat {
+        /// Steps before giving up.
+        steps: u64,
+    },
+    /// Pollard's rho, given up after `iterations` steps so that the stages after it
+    /// get their turn.
+    Rho {
+        /// Steps of the sequence before giving up.
+        iterations: u64,
+    },
+    /// Lenstra's elliptic curve method, finds `p` if the order of one of the curves
+    /// modulo `p` has only prime powers up to `b1` and a single prime up to `b2`. The
+    /// curves run in parallel.
+    Ecm {
+        /// Stage 1 bound.
+        b1: u64,
+        /// Stage 2 bound, no stage 2 unless it is above `b1`.
+        b2: u64,
+        /// Number of curves tried.
+        curves: u32,
+    },
+    /// The self-initializing quadratic sieve, see [`siqs`](crate::siqs::siqs). It
+    /// splits every cofactor that is not a prime power, in a time that grows with the
+    /// size of the cofactor: a fraction of a second for 50 digits, minutes for 70.
+    Siqs,
+}
+
+impl Stage {
+    /// ECM with the bounds and curve count for prime factors of up to `digits` decimal
+    /// digits, between 15 and 40.
+    ///
+    /// `b1` and the curve counts follow the table of GMP-ECM, `b2` is `100 * b1`.
+    ///
+    /// ```
+    /// use prime_factorization::factor::Stage;
+    ///
+    /// assert_eq!(Stage::Ecm { b1: 50_000, b2: 5_000_000, curves: 300 }, Stage::ecm(25));
+    /// ```
+    pub fn ecm(digits: u32) -> Stage {
+        // (digits, b1, curves)
+        const TABLE: [(u32, u64, u32); 6] = [
+            (15, 2_000, 25),
+            (20, 11_000, 90),
+            (25, 50_000, 300),
+            (30, 250_000, 700),
+            (35, 1_000_000, 1_800),
+            (40, 3_000_000, 5_100),
+        ];
+        let (_, b1, curves) = TABLE
+            .into_iter()
+            .find(|&(max, _, _)| digits <= max)
+            .unwrap_or(TABLE[TABLE.len() - 1]);
+        Stage::Ecm {
+            b1,
+            b2: 100 * b1,
+            curves,
+        }
+    }
+}
+
+/// The stages tried on a composite cofactor, in order.
+///
+/// Trial division always comes first and Pollard's rho without a limit last, a stage
+/// that fails only costs time. The default is [`Pipeline::for_digits`] with 20 digits:
+///
+/// ```
+/// use prime_factorization::factor::{Pipeline, Stage};
+///
+/// let pipeline = Pipeline::new()
+///     .stage(Stage::PMinusOne { b1: 10_000, b2: 1_000_000 })
+///     .stage(Stage::PPlusOne { b1: 10_000 })
+///     .stage(Stage::Rho { iterations: 1 << 20 })
+///     .stage(Stage::ecm(20))
+///     .stage(Stage::Siqs);
+/// assert_eq!(Pipeline::default(), pipeline);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Pipeline {
+    stages: Vec<Stage>,
+}
+
+impl Pipeline {
+    /// No stages, only the size-based dispatch every pipeline ends with: trial
+    /// division up to 22 bits, then a few steps of Hart's one line method, SQUFOF up
+    /// to 32 bits and Pollard's rho above.
+    pub fn new() -> Self {
+        Pipeline { stages: Vec::new() }
+    }
+
+    /// p − 1 and p + 1 with small bounds, rho for factors of up to about 12 digits,
+    /// ECM for factors of up to `digits` digits, see [`Stage::ecm`], and the quadratic
+    /// sieve for the rest.
+    pub fn for_digits(digits: u32) -> Self {
+        Pipeline::new()
+            .stage(Stage::PMinusOne {
+                b1: 10_000,
+                b2: 1_000_000,
+            })
+            .stage(Stage::PPlusOne { b1: 10_000 })
+            .stage(Stage::Rho {
+                iterations: RHO_ITERATIONS,
+            })
+            .stage(Stage::ecm(digits))
+            .stage(Stage::Siqs)
+    }
+
+    /// Adds `stage` after the stages added before.
+    pub fn stage(mut self, stage: Stage) -> Self {
+        self.stages.push(stage);
+        self
+    }
+
+    /// The stages in the order they are tried.
+    pub fn stages(&self) -> &[Stage] {
+        &self.stages
+    }
+
+    // the first divisor a stage finds, the ring is only set up if there is a stage
+    fn split<R>(&self, ring: impl FnOnce() -> R) -> Option<(R::T, Method)>
+    where
+        R: Residues + Sync,
+        R::T: Send + Sync,
+    {
+        if self.stages.is_empty() {
+            return None;
+        }
+        let ring = ring();
+        self.stages.iter().find_map(|stage| match *stage {
+            Stage::PMinusOne { b1, b2 } => {
+                p_minus_one(&ring, b1, b2).map(|d| (d, Method::PMinusOne))
+            }
+            Stage::PPlusOne { b1 } => p_plus_one(&ring, b1).map(|d| (d, Method::PPlusOne)),
+            Stage::Fermat { steps } => {
+                fermat(&ring.big_modulus(), steps).map(|d| (ring.narrow(d), Method::Fermat))
+            }
+            Stage::Rho { iterations } => {
+                pollard_brent_ring(&ring, iterations).map(|d| (d, Method::Rho))
+            }
+            Stage::Ecm { b1, b2, curves } => ecm(&ring, b1, b2, curves).map(|d| (d, Method::Ecm)),
+            Stage::Siqs => siqs(&ring.big_modulus()).map(|d| (ring.narrow(d), Method::Siqs)),
+        })
+    }
+}
+
+impl Default for Pipeline {
+    fn default() -> Self {
+        Pipeline::for_digits(20)
+    }
+}
+
+impl Factor for u32 {
+    fn factor(self) -> Vec<(u32, u32)> {
+        without_methods(self.factor_with(&Pipeline::new()))
+    }
+
+    fn factor_with(self, pipeline: &Pipeline) -> Vec<(u32, u32, Method)> {
+        (self as u64)
+            .factor_with(pipeline)
+            .into_iter()
+            .map(|(p, exp, method)| (p as u32, exp, method))
+            .collect()
+    }
+}
+
+impl Factor for u64 {
+    fn factor(self) -> Vec<(u64, u32)> {
+        factor(self)
+    }
+
+    fn factor_with(mut self, pipeline: &Pipeline) -> Vec<(u64, u32, Method)> {
+        let mut primes = Vec::new();
+        if self < 2 {
+            return Vec::new();
+        }
+
+        // small factors first, most numbers are done after this
+        for p in [2, 3] {
+            while self.is_multiple_of(p) {
+                primes.push((p, Method::TrialDivision));
+                self /= p;
+            }
+        }
+        let mut p = 5;
+        while p < TRIAL_LIMIT && p * p <= self {
+            for d in [p, p + 2] {
+                while self.is_multiple_of(d) {
+                    primes.push((d, Method::TrialDivision));
+                    self /= d;
+                }
+            }
+            p += 6; // all primes >3 are of the form 6k ± 1
+        }
+
+        // the cofactor has no factor below TRIAL_LIMIT, the pipeline splits the rest
+        let found = cofactor_method(&primes);
+        split(self, pipeline, found, &mut primes);
+        group(primes)
+    }
+}
+
+impl Factor for u128 {
+    /// The default [`Pipeline`] in Montgomery form. Pollard's rho finds factors up to
+    /// about 10^12 quickly, its time grows with the square root of the second largest
+    /// prime factor, unless one of the stages finds it first.
+    fn factor(self) -> Vec<(u128, u32)> {
+        without_methods(self.factor_with(&Pipeline::default()))
+    }
+
+    fn factor_with(self, pipeline: &Pipeline) -> Vec<(u128, u32, Method)> {
+        let mut n = self;
+        if let Ok(n) = u64::try_from(n) {
+            return n
+                .factor_with(pipeline)
+                .into_iter()
+                .map(|(p, exp, method)| (p as u128, exp, method))
+                .collect();
+        }
+
+        let mut primes = Vec::new();
+        let mut p = 2;
+        while p < TRIAL_LIMIT as u128 && p * p <= n {
+            while n.is_multiple_of(p) {
+                primes.push((p, Method::TrialDivision));
+                n /= p;
+            }
+            p += if p == 2 { 1 } else { 2 };
+        }
+
+        let found = cofactor_method(&primes);
+        split_wide(n, pipeline, found, &mut primes);
+        group(primes)
+    }
+}
+
+impl Factor for BigUint {
+    /// The default [`Pipeline`] on [`BigUint`]. Unless a stage finds them, the time grows
+    /// with the square root of the second largest prime factor, so this is for numbers
+    /// with at most one large factor.
+    fn factor(self) -> Vec<(BigUint, u32)> {
+        without_methods(self.factor_with(&Pipeline::default()))
+    }
+
+    fn factor_with(self, pipeline: &Pipeline) -> Vec<(BigUint, u32, Method)> {
+        if let Some(n) = self.to_u128() {
+            return n
+                .factor_with(pipeline)
+                .into_iter()
+                .map(|(p, exp, method)| (BigUint::from(p), exp, method))
+                .collect();
+        }
+
+        let mut n = self;
+        let mut primes = Vec::new();
+        let mut p = 2;
+        while p < TRIAL_LIMIT {
+            while n.rem_u64(p) == 0 {
+                primes.push((BigUint::from(p), Method::TrialDivision));
+                n = n.div_rem_u64(p).0;
+            }
+            p += if p == 2 { 1 } else { 2 };
+        }
+
+        let found = cofactor_method(&primes);
+        split_big(n, pipeline, found, &mut primes);
+        group(primes)
+    }
+}
+
+/// Complete prime factorization of `n` as `(prime, exponent)`, sorted by prime.
+///
+/// 0 and 1 have no prime factors and give an empty list.
+pub fn factor(n: u64) -> Vec<(u64, u32)> {
+    without_methods(n.factor_with(&Pipeline::new()))
+}
+
+/// A divisor of `n` other than 1 and `n`, and the method that found it, picked by the
+/// size of `n`. `None` for primes, 0 and 1.
+///
+/// Trial division is fastest up to 22 bits. SQUFOF and Pollard's rho are about even
+/// up to 32 bits, SQUFOF ahead on two primes of the same size, and rho is up to
+/// three times as fast above. A few steps of Hart's one line factoring come first at
+/// every size, they split the product of two primes whose ratio is close to a
+/// fraction with a small denominator at once, such as two primes of a narrow range.
+///
+/// ```
+/// use prime_factorization::factor::{Method, find_divisor};
+///
+/// assert_eq!(Some((1_009, Method::TrialDivision)), find_divisor(1_009 * 1_013));
+/// assert_eq!(Some((1_000_000_007, Method::Hart)), find_divisor(1_000_000_007 * 1_000_000_009));
+/// assert_eq!(None, find_divisor(1_000_000_007));
+/// ```
+pub fn find_divisor(n: u64) -> Option<(u64, Method)> {
+    (n >= 4 && !n.prime()).then(|| dispatch(n))
+}
+
+/// A divisor of `n` other than 1 and `n` by a single `method`: trial division, Hart's
+/// one line factoring, SQUFOF or Pollard's rho. `None` for primes, for the other
+/// methods, and if Hart or SQUFOF gave up.
+///
+/// ```
+/// use prime_factorization::factor::{Method, divisor_by};
+///
+/// let n = 1_000_003 * 1_000_033;
+/// assert_eq!(Some(1_000_003), divisor_by(n, Method::TrialDivision));
+/// assert!(divisor_by(n, Method::Squfof).is_some_and(|d| d == 1_000_003 || d == 1_000_033));
+/// assert_eq!(None, divisor_by(n, Method::Ecm));
+/// ```
+pub fn divisor_by(n: u64, method: Method) -> Option<u64> {
+    if n < 4 || n.prime() {
+        return None;
+    }
+    match method {
+        Method::TrialDivision => Some(smallest_divisor(n)),
+        Method::Hart => crate::hart::hart(n),
+        Method::Squfof => squfof(n),
+        Method::Rho => Some(pollard_brent(n)),
+        _ => None,
+    }
+}
+
+// find_divisor for a composite n
+fn dispatch(n: u64) -> (u64, Method) {
+    let bits = u64::BITS - n.leading_zeros();
+    if n.is_multiple_of(2) || bits <= TRIAL_BITS {
+        return (smallest_divisor(n), Method::TrialDivision);
+    }
+    if let Some(d) = hart_steps(n, HART_STEPS) {
+        return (d, Method::Hart);
+    }
+    if bits <= SQUFOF_BITS
+        && let Some(d) = squfof(n)
+    {
+        return (d, Method::Squfof);
+    }
+    (pollard_brent(n), Method::Rho)
+}
+
+// the smallest prime factor of a composite n
+fn smallest_divisor(n: u64) -> u64 {
+    for p in [2, 3] {
+        if n.is_multiple_of(p) {
+            return p;
+        }
+    }
+    let mut p = 5;
+    while p * p <= n {
+        for d in [p, p + 2] {
+            if n.is_multiple_of(d) {
+                return d;
+            }
+        }
+        p += 6;
+    }
+    unreachable!("a composite has a factor up to its square root")
+}
+
+// the cofactor left by trial division was found by it, unless there was nothing to divide
+fn cofactor_method<T>(primes: &[(T, Method)]) -> Method {
+    if primes.is_empty() {
+        Method::Primality
+    } else {
+        Method::TrialDivision
+    }
+}
+
+// sort the prime factors and count repeats into exponents, keeping the first method
+fn group<T: Ord>(mut primes: Vec<(T, Method)>) -> Vec<(T, u32, Method)> {
+    // stable, so a prime found twice keeps the method that found it first
+    primes.sort_by(|a, b| a.0.cmp(&b.0));
+    let mut factors: Vec<(T, u32, Method)> = Vec::new();
+    for (p, method) in primes {
+        match factors.last_mut() {
+            Some((q, exp, _)) if *q == p => *exp += 1,
+            _ => factors.push((p, 1, method)),
+        }
+    }
+    factors
+}
+
+fn without_methods<T>(factors: Vec<(T, u32, Method)>) -> Vec<(T, u32)> {
+    factors.into_iter().map(|(p, exp, _)| (p, exp)).collect()
+}
+
+// n was split off by found, which is passed on to its prime factors
+fn split(n: u64, pipeline: &Pipeline, found: Method, primes: &mut Vec<(u64, Method)>) {
+    if n == 1 {
+        return;
+    }
+    if n.prime() {
+        primes.push((n, found));
+        return;
+    }
+
+    let (d, method) = match pipeline.split(|| Montgomery::new(n as u128)) {
+        Some((d, method)) => (d as u64, method),
+        None => dispatch(n),
+    };
+    split(d, pipeline, method, primes);
+    split(n / d, pipeline, method, primes);
+}
+
+// pollard's rho with brent's cycle detection, returns a non-trivial divisor of composite n
+fn pollard_brent(n: u64) -> u64 {
+    if n.is_multiple_of(2) {
+        return 2;
+    }
+
+    // a failed run only means an unlucky polynomial, retry with the next constant
+    for c in 1..n {
+        let f = |x: u64| ((x as u128 * x as u128 + c as u128) % n as u128) as u64;
+
+        let (mut x, mut y, mut ys) = (0, 2, 2);
+        let (mut g, mut q, mut r) = (1, 1, 1);
+
+        while g == 1 {
+            x = y;
+            for _ in 0..r {
+                y = f(y);
+            }
+
+            let mut k = 0;
+            while k < r && g == 1 {
+                ys = y;
+                for _ in 0..BATCH.min(r - k) {
+                    y = f(y);
+                    q = mul_mod(q, x.abs_diff(y), n);
+                }
+                g = gcd(q, n);
+                k += BATCH;
+            }
+            r *= 2;
+        }
+
+        // the batch overshot, step back one at a time
+        if g == n {
+            loop {
+                ys = f(ys);
+                g = gcd(x.abs_diff(ys), n);
+                if g > 1 {
+                    break;
+                }
+            }
+        }
+
+        if g != n {
+            return g;
+        }
+    }
+
+    unreachable!("pollard rho called on a prime")
+}
+
+fn split_wide(n: u128, pipeline: &Pipeline, found: Method, primes: &mut Vec<(u128, Method)>) {
+    // once a cofactor fits into u64, the narrow path is faster
+    if let Ok(n) = u64::try_from(n) {
+        let mut narrow = Vec::new();
+        split(n, pipeline, found, &mut narrow);
+        primes.extend(narrow.into_iter().map(|(p, method)| (p as u128, method)));
+        return;
+    }
+    if n.prime() {
+        primes.push((n, found));
+        return;
+    }
+
+    let (d, method) = pipeline
+        .split(|| Montgomery::new(n))
+        .unwrap_or_else(|| (rho(Montgomery::new(n)), Method::Rho));
+    split_wide(d, pipeline, method, primes);
+    split_wide(n / d, pipeline, method, primes);
+}
+
+fn split_big(n: BigUint, pipeline: &Pipeline, found: Method, primes: &mut Vec<(BigUint, Method)>) {
+    if let Some(n) = n.to_u128() {
+        let mut wide = Vec::new();
+        split_wide(n, pipeline, found, &mut wide);
+        primes.extend(
+            wide.into_iter()
+                .map(|(p, method)| (BigUint::from(p), method)),
+        );
+        return;
+    }
+    if (&n).prime() {
+        primes.push((n, found));
+        return;
+    }
+
+    let (d, method) = pipeline
+        .split(|| BigMontgomery::new(&n))
+        .unwrap_or_else(|| (rho(BigMontgomery::new(&n)), Method::Rho));
+    let cofactor = &n / &d;
+    split_big(d, pipeline, method, primes);
+    split_big(cofactor, pipeline, method, primes);
+}
+
+// the fallback after every stage, rho without a limit always ends up splitting n
+fn rho<R: Residues>(ring: R) -> R::T {
+    pollard_brent_ring(&ring, u64::MAX).expect("pollard rho without a limit")
+}
+
+// pollard_brent on any ring, for n >= 2^64, None if limit steps found no divisor
+fn pollard_brent_ring<R: Residues>(ring: &R, limit: u64) -> Option<R::T> {
+    let zero = ring.residue(0);
+    let mut steps = 0;
+
+    for c in 1.. {
+        let c = ring.residue(c);
+        let f = |x: &R::T| ring.add(&ring.mul(x, x), &c);
+
+        let (mut x, mut ys);
+        let mut y = ring.residue(2);
+        let (mut q, mut r) = (ring.residue(1), 1);
+        'search: loop {
+            x = y.clone();
+            for _ in 0..r {
+                y = f(&y);
+            }
+
+            let mut k = 0;
+            while k < r {
+                ys = y.clone();
+                for _ in 0..BATCH.min(r - k) {
+                    y = f(&y);
+                    q = ring.mul(&q, &ring.sub(&x, &y));
+                }
+                if q == zero {
+                    break 'search;
+                }
+                if let Some(d) = ring.divisor(&q) {
+                    return Some(d);
+                }
+                steps += BATCH.min(r - k);
+                if steps >= limit {
+                    return None;
+                }
+                k += BATCH;
+            }
+            r *= 2;
+        }
+
+        // the batch overshot, step back one at a time
+        loop {
+            ys = f(&ys);
+            let diff = ring.sub(&x, &ys);
+            if let Some(d) = ring.divisor(&diff) {
+                return Some(d);
+            }
+            if diff == zero {
+                break;
+            }
+        }
+    }
+
+    unreachable!("pollard rho ran out of constants")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn product(factors: &[(u64, u32)]) -> u64 {
+        factors.iter().map(|&(p, exp)| p.pow(exp)).product()
+    }
+
+    #[test]
+    fn factor_small() {
+        assert_eq!(Vec::<(u64, u32)>::new(), factor(0));
+        assert_eq!(Vec::<(u64, u32)>::new(), factor(1));
+        assert_eq!(vec![(2, 1)], factor(2));
+        assert_eq!(vec![(2, 3), (3, 2), (5, 1)], factor(360));
+        assert_eq!(vec![(7, 1), (11, 1), (13, 1)], factor(1001));
+    }
+
+    #[test]
+    fn factor_products() {
+        for n in 2..100_000 {
+            let factors = factor(n);
+            assert_eq!(n, product(&factors));
+            assert!(factors.iter().all(|&(p, _)| p.prime()));
+            assert!(factors.windows(2).all(|w| w[0].0 < w[1].0));
+        }
+    }
+
+    #[test]
+    fn factor_large() {
+        assert_eq!(
+            vec![
+                (3, 1),
+                (5, 1),
+                (17, 1),
+                (257, 1),
+                (641, 1),
+                (65537, 1),
+                (6700417, 1)
+            ],
+            factor(u64::MAX)
+        );
+        assert_eq!(
+            vec![(4_294_967_279, 1), (4_294_967_291, 1)],
+            factor(4_294_967_291 * 4_294_967_279)
+        );
+        assert_eq!(vec![(1_000_003, 3)], factor(1_000_003u64.pow(3)));
+        assert_eq!(vec![(u64::MAX - 58, 1)], factor(u64::MAX - 58));
+    }
+
+    #[test]
+    fn factor_widths() {
+        for n in 0..10_000u32 {
+            let wide: Vec<(u64, u32)> =
+                n.factor().into_iter().map(|(p, e)| (p as u64, e)).collect();
+            assert_eq!(factor(n as u64), wide);
+            let wide: Vec<(u64, u32)> = (n as u128)
+                .factor()
+                .into_iter()
+                .map(|(p, e)| (p as u64, e))
+                .collect();
+            assert_eq!(factor(n as u64), wide);
+        }
+        assert_eq!(vec![(65_521, 2)], (65_521u32 * 65_521).factor());
+        assert_eq!(vec![(u32::MAX - 4, 1)], (u32::MAX - 4).factor());
+    }
+
+    #[test]
+    fn factor_wide() {
+        // 2^128 - 1 = (2^64 + 1) * (2^32 + 1) * ...
+        assert_eq!(
+            vec![
+                (3, 1),
+                (5, 1),
+                (17, 1),
+                (257, 1),
+                (641, 1),
+                (65_537, 1),
+                (274_177, 1),
+                (6_700_417, 1),
+                (67_280_421_310_721, 1)
+            ],
+            u128::MAX.factor()
+        );
+        assert_eq!(vec![((1 << 127) - 1, 1)], ((1u128 << 127) - 1).factor());
+        assert_eq!(vec![(2, 100)], (1u128 << 100).factor());
+        assert_eq!(
+            vec![(1_000_003, 2), (18_446_744_073_709_551_557, 1)],
+            (1_000_003u128 * 1_000_003 * 18_446_744_073_709_551_557).factor()
+        );
+    }
+
+    #[test]
+    fn factor_wide_products() {
+        let factors = [
+            (vec![
+                (1_000_000_007, 1),
+                (10_000_000_019, 1),
+                (1_000_000_000_000_000_003, 1),
+            ]),
+            (vec![(4_294_967_291, 2), (4_294_967_279, 1), (65_521, 1)]),
+            (vec![
+                (2, 3),
+                (97, 1),
+                (1_000_003, 1),
+                (10_000_000_019, 1),
+                (4_294_967_311, 1),
+            ]),
+            (vec![(999_999_937, 4)]),
+        ];
+        for expected in factors {
+            let mut expected: Vec<(u128, u32)> = expected;
+            expected.sort_unstable();
+            let n: u128 = expected.iter().map(|&(p, e)| p.pow(e)).product();
+            assert!(n > u64::MAX as u128);
+            assert_eq!(expected, n.factor(), "{n}");
+        }
+    }
+
+    #[test]
+    fn factor_big() {
+        let big = |s: &str| s.parse::<BigUint>().unwrap();
+        let factors = |n: BigUint| -> Vec<(String, u32)> {
+            n.factor()
+                .into_iter()
+                .map(|(p, exp)| (p.to_string(), exp))
+                .collect()
+        };
+
+        assert_eq!(
+            vec![("3".to_string(), 2), ("7".to_string(), 1)],
+            factors(BigUint::from(63u64))
+        );
+        // 2^192 - 1 mixes small, wide and narrow factors
+        let n = &(&BigUint::one() << 192) - &BigUint::one();
+        let expected: Vec<(String, u32)> = [
+            ("3", 2),
+            ("5", 1),
+            ("7", 1),
+            ("13", 1),
+            ("17", 1),
+            ("97", 1),
+            ("193", 1),
+            ("241", 1),
+            ("257", 1),
+            ("641", 1),
+            ("673", 1),
+            ("65537", 1),
+            ("6700417", 1),
+            ("22253377", 1),
+            ("18446744069414584321", 1),
+        ]
+        .into_iter()
+        .map(|(p, exp)| (p.to_string(), exp))
+        .collect();
+        assert_eq!(expected, factors(n.clone()));
+        let product = n
+            .clone()
+            .factor()
+            .iter()
+            .fold(BigUint::one(), |acc, (p, exp)| &acc * &p.pow(*exp));
+        assert_eq!(n, product);
+
+        // a 50 digit prime times two 10 digit primes and a square
+        let p = big("37975227936943673922808872755445627854565536638199");
+        let n = &(&(&p * &BigUint::from(1_000_000_007u64)) * &BigUint::from(10_000_000_019u64))
+            * &BigUint::from(65_537u64 * 65_537);
+        assert_eq!(
+            vec![
+                ("65537".to_string(), 2),
+                ("1000000007".to_string(), 1),
+                ("10000000019".to_string(), 1),
+                (p.to_string(), 1)
+            ],
+            factors(n)
+        );
+    }
+
+    #[test]
+    fn pipeline_methods() {
+        let n: u64 = 2 * 1_000_001_269;
+        assert_eq!(
+            vec![
+                (2, 1, Method::TrialDivision),
+                (1_000_001_269, 1, Method::TrialDivision)
+            ],
+            n.factor_with(&Pipeline::default())
+        );
+        assert_eq!(
+            vec![(1_000_001_269, 1, Method::Primality)],
+            1_000_001_269u64.factor_with(&Pipeline::new())
+        );
+
+        // 1_000_001_269 - 1 = 2^2 * 3^3 * 7 * 17^2 * 23 * 199
+        // 1_000_004_633 + 1 = 2 * 3^2 * 17 * 31 * 271 * 389
+        let n: u64 = 1_000_001_269 * 1_000_004_633;
+        // two primes this close are hart's
+        assert_eq!(Method::Hart, n.factor_with(&Pipeline::new())[0].2);
+        let spread: u64 = 1_000_003 * 1_000_000_000_039;
+        assert_eq!(Method::Rho, spread.factor_with(&Pipeline::new())[0].2);
+        let small: u64 = 40_009 * 50_021;
+        assert_eq!(Method::Squfof, small.factor_with(&Pipeline::new())[0].2);
+        let pm1 = Pipeline::new().stage(Stage::PMinusOne { b1: 1000, b2: 1000 });
+        assert_eq!(Method::PMinusOne, n.factor_with(&pm1)[0].2);
+        let pp1 = Pipeline::new().stage(Stage::PPlusOne { b1: 1000 });
+        assert_eq!(Method::PPlusOne, n.factor_with(&pp1)[0].2);
+        // the first stage that splits wins
+        let both = pp1.clone().stage(Stage::PMinusOne { b1: 1000, b2: 1000 });
+        assert_eq!(Method::PPlusOne, n.factor_with(&both)[0].2);
+        assert_eq!(n.factor(), without_methods(n.factor_with(&both)));
+    }
+
+    #[test]
+    fn pipeline_wide_and_big() {
+        // 1_000_000_033 - 1 = 2^5 * 3 * 127 * 82_021, only stage 2 gets it
+        let n: u128 = 1_000_000_033 * 1_000_000_007 * 18_446_744_073_709_551_557;
+        let stage_one = Pipeline::new().stage(Stage::PMinusOne { b1: 1000, b2: 0 });
+        let stage_two = Pipeline::new().stage(Stage::PMinusOne {
+            b1: 1000,
+            b2: 100_000,
+        });
+        assert!(
+            n.factor_with(&stage_one)
+                .iter()
+                .all(|&(_, _, method)| method == Method::Rho)
+        );
+        // p - 1 only splits off 1_000_000_033, rho the rest
+        assert_eq!(
+            vec![
+                (1_000_000_007, 1, Method::Rho),
+                (1_000_000_033, 1, Method::PMinusOne),
+                (18_446_744_073_709_551_557, 1, Method::Rho)
+            ],
+            n.factor_with(&stage_two)
+        );
+
+        // a 50 digit prime times 1_000_004_633 and 1_000_000_007
+        let p: BigUint = "37975227936943673922808872755445627854565536638199"
+            .parse()
+            .unwrap();
+        let n = &(&p * &BigUint::from(1_000_004_633u64)) * &BigUint::from(1_000_000_007u64);
+        let pp1 = Pipeline::new().stage(Stage::PPlusOne { b1: 1000 });
+        assert_eq!(
+            vec![
+                (BigUint::from(1_000_000_007u64), 1, Method::Rho),
+                (BigUint::from(1_000_004_633u64), 1, Method::PPlusOne),
+                (p, 1, Method::Rho),
+            ],
+            n.factor_with(&pp1)
+        );
+    }
+
+    #[test]
+    fn pipeline_rho_and_ecm() {
+        // p - 1 and p + 1 of both primes have a factor above 10^6, rho would need
+        // about 3 * 10^7 steps
+        let (p, q) = (1_000_000_000_000_091u128, 3_000_000_000_000_301u128);
+        let stages = Pipeline::new()
+            .stage(Stage::PMinusOne {
+                b1: 10_000,
+                b2: 1_000_000,
+            })
+            .stage(Stage::PPlusOne { b1: 10_000 })
+            .stage(Stage::Rho { iterations: 1000 })
+            .stage(Stage::Ecm {
+                b1: 2_000,
+                b2: 200_000,
+                curves: 200,
+            });
+        assert_eq!(
+            vec![(p, 1, Method::Ecm), (q, 1, Method::Ecm)],
+            (p * q).factor_with(&stages)
+        );
+
+        // the rho stage gets 10 digit factors well within its steps
+        let n = 1_000_000_007u128 * 1_000_000_009 * 1_000_000_021;
+        let rho = Pipeline::new().stage(Stage::Rho {
+            iterations: RHO_ITERATIONS,
+        });
+        assert!(
+            n.factor_with(&rho)
+                .iter()
+                .all(|&(_, _, method)| method == Method::Rho)
+        );
+        assert_eq!(None, pollard_brent_ring(&Montgomery::new(p * q), 1000));
+
+        // a 12 digit factor of a 165 bit number
+        let m = &(&BigUint::one() << 127) - &BigUint::one();
+        let n = &m * &BigUint::from(100_000_000_003u64);
+        assert_eq!(
+            vec![
+                (BigUint::from(100_000_000_003u64), 1, Method::Ecm),
+                (m, 1, Method::Ecm)
+            ],
+            n.factor_with(&stages)
+        );
+    }
+
+    #[test]
+    fn ecm_bounds() {
+        assert_eq!(
+            Stage::Ecm {
+                b1: 2_000,
+                b2: 200_000,
+                curves: 25
+            },
+            Stage::ecm(10)
+        );
+        assert_eq!(Stage::ecm(21), Stage::ecm(25));
+        assert_eq!(Stage::ecm(40), Stage::ecm(60));
+        assert_eq!(Pipeline::default(), Pipeline::for_digits(20));
+    }
+}
@@ -0,0 +1,119 @@
+//! Fermat's factorization method.
+//!
+//! An odd `n = pq` is the difference `a^2 - b^2` of the squares of `a = (p + q) / 2`
+//! and `b = (q - p) / 2`. Counting `a` up from `⌈√n⌉` until `a^2 - n` is a square
+//! takes `(√q - √p)^2 / 2` steps, a single one if `q - p` is below about `2 n^(1/4)`,
+//! whatever the size of `n`. Products of two primes picked close to each other, as
+//! from a single random start, fall at once, while rho and ECM need about `√p` steps
+//! or lucky curves.
+//!
+//! ```
+//! use prime_factorization::{bigint::BigUint, fermat::fermat};
+//!
+//! // two primes just above 10^30
+//! let p: BigUint = "1000000000000000000000000000057".parse().unwrap();
+//! let q: BigUint = "1000000000000000000000000000099".parse().unwrap();
+//! assert_eq!(Some(p.clone()), fermat(&(&p * &q), 1));
+//! ```
+
+use crate::{bigint::BigUint, roots::exact_sqrt_big};
+
+// a^2 - n is checked against the squares modulo these before taking its root, bit x
+// of the mask is set if x is a square modulo m
+const SQUARE_FILTERS: [(u64, u128); 4] = [
+    (64, squares_mod(64)),
+    (63, squares_mod(63)),
+    (65, squares_mod(65)),
+    (11, squares_mod(11)),
+];
+
+/// A divisor of `n` other than 1 and `n`, found by Fermat's method within `steps`
+/// steps, the smaller of the two closest to `√n`.
+///
+/// `None` if it gave up, always for primes and below 4. Even `n` give 2.
+pub fn fermat(n: &BigUint, steps: u64) -> Option<BigUint> {
+    if *n < BigUint::from(4u64) {
+        return None;
+    }
+    // a^2 - b^2 is never 2 mod 4
+    if n.is_even() {
+        return Some(BigUint::from(2u64));
+    }
+    let mut a = n.isqrt();
+    if &a * &a == *n {
+        return Some(a);
+    }
+    a = &a + &BigUint::one();
+
+    // r = a^2 - n, moving on to a + 1 adds 2a + 1
+    let mut r = &(&a * &a) - n;
+    for _ in 0..steps {
+        if let Some(b) = exact_sqrt(&r) {
+            // a + b = n and a - b = 1 once a passed the largest useful value
+            let d = &a - &b;
+            return (!d.is_one()).then_some(d);
+        }
+        r = &(&r + &(&a << 1)) + &BigUint::one();
+        a = &a + &BigUint::one();
+    }
+    None
+}
+
+// √r if r is a square
+fn exact_sqrt(r: &BigUint) -> Option<BigUint> {
+    let squares = SQUARE_FILTERS
+        .iter()
+        .all(|&(m, mask)| mask >> r.rem_u64(m) & 1 == 1);
+    if !squares {
+        return None;
+    }
+    exact_sqrt_big(r)
+}
+
+const fn squares_mod(m: u64) -> u128 {
+    let mut mask = 0;
+    let mut y = 0;
+    while y < m {
+        mask |= 1 << (y * y % m);
+        y += 1;
+    }
+    mask
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn big(n: u128) -> BigUint {
+        BigUint::from(n)
+    }
+
+    #[test]
+    fn close_primes() {
+        for (p, q) in [
+            (3u128, 5),
+            (1_000_003, 1_000_033),
+            (1_000_000_007, 1_000_000_009),
+            (18_446_744_073_709_551_557, 18_446_744_073_709_551_629),
+        ] {
+            assert_eq!(Some(big(p)), fermat(&big(p * q), 1), "{p} * {q}");
+        }
+        // 1191 steps above ⌈√n⌉
+        let (p, q) = (1_000_003u128, 1_100_009);
+        assert_eq!(None, fermat(&big(p * q), 1));
+        assert_eq!(Some(big(p)), fermat(&big(p * q), 1 << 12));
+        assert_eq!(Some(big(1_000_003)), fermat(&big(1_000_003 * 1_000_003), 0));
+    }
+
+    #[test]
+    fn degenerate_inputs() {
+        for n in [0u128, 1, 2, 3, 5, 7, 1_000_000_007] {
+            assert_eq!(None, fermat(&big(n), 1 << 10), "{n}");
+        }
+        assert_eq!(Some(big(2)), fermat(&big(1 << 70), 1));
+        // 15 = 4^2 - 1^2 = 8^2 - 7^2, the second is trivial
+        assert_eq!(Some(big(3)), fermat(&big(15), 10));
+        // 3 * 1_000_003 needs about 500_000 steps
+        assert_eq!(None, fermat(&big(3 * 1_000_003), 1_000));
+    }
+}
@@ -0,0 +1,85 @@
+//! Hart's one line factoring algorithm.
+//!
+//! For `i = 1, 2, ...` the square `s^2` of `s = ⌈√(in)⌉` is only a little above `in`,
+//! so `s^2 mod n` is small and now and then a square `t^2`. Then `s^2 ≡ t^2 (mod n)`
+//! and `gcd(s - t, n)` is a factor. It needs about `n^(1/3)` steps, fewer for products
+//! of two primes whose ratio is close to a fraction with a small denominator, which
+//! it splits in a handful of steps whatever their size.
+//!
+//! ```
+//! use prime_factorization::hart::hart;
+//!
+//! assert_eq!(Some(65_521), hart(65_521 * 65_537));
+//! ```
+
+use crate::{
+    arith::gcd,
+    roots::{exact_sqrt, icbrt, wide_isqrt},
+};
+
+/// A divisor of `n` other than 1 and `n`, found by Hart's one line factoring.
+///
+/// Gives up after `4 n^(1/3)` steps, which are enough for `n` without prime factors
+/// below `n^(1/3)`. `None` if it gave up, always for primes.
+pub fn hart(n: u64) -> Option<u64> {
+    hart_steps(n, 4 * icbrt(n) + 16)
+}
+
+// hart with at most `limit` steps
+pub(crate) fn hart_steps(n: u64, limit: u64) -> Option<u64> {
+    if n < 4 {
+        return None;
+    }
+    // s^2 - t^2 is never 2 mod 4
+    if n.is_multiple_of(2) {
+        return Some(2);
+    }
+    for i in 1..=limit {
+        // ⌈√(in)⌉
+        let ni = n as u128 * i as u128;
+        let r = wide_isqrt(ni) as u128;
+        let s = if r * r == ni { r } else { r + 1 };
+        // s^2 - in < 2s + 1 is s^2 mod n without a division
+        let m = (s * s - ni) as u64;
+        if let Some(t) = exact_sqrt(m) {
+            let g = gcd(((s - t as u128) % n as u128) as u64, n);
+            if g != 1 && g != n {
+                return Some(g);
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Prime, factor};
+
+    #[test]
+    fn splits_semiprimes() {
+        for (p, q) in [
+            (11, 13),
+            (1_009, 1_000_003),
+            (65_521, 65_537),
+            (1_000_003, 1_000_033),
+            (1_000_000_007, 998_244_353),
+        ] {
+            let d = hart(p * q).unwrap();
+            assert!(d == p || d == q, "{p} * {q}");
+        }
+        assert_eq!(Some(1_000_003), hart(1_000_003 * 1_000_003));
+    }
+
+    #[test]
+    fn composites() {
+        for n in (4..100_000u64).filter(|n| !n.prime()) {
+            match hart(n) {
+                Some(d) => assert!(n.is_multiple_of(d) && d != 1 && d != n, "{n}"),
+                // only a factor below the cube root may get away
+                None => assert!(factor(n)[0].0.pow(3) < n, "{n}"),
+            }
+        }
+        assert_eq!(None, hart(1_000_000_007));
+    }
+}
@@ -0,0 +1,214 @@
+//! Prime numbers and prime factorization.
+//!
+//! - primality: [`Prime`], for `u32`, `u64` and `u128`, and [`ProbablePrime`]
+//! - prime ranges: [`collect_primes`], [`collect_primes_u128`], [`sieve::segmented_sieve`]
+//! - factorization: [`factor()`], [`Factor`] for `u32`, `u64` and `u128`, with the
+//!   stages of a [`factor::Pipeline`]: p − 1, p + 1, rho, elliptic curves and the
+//!   quadratic sieve of [`siqs`], and [`factor::find_divisor`] to pick among trial
+//!   division, [`hart`], [`squfof`] and rho for a `u64`
+//! - weak RSA moduli: [`audit`], with [`fermat`] for close primes, and primes shared
+//!   across many moduli: [`batch`]
+//! - semiprime enumeration: [`semiprime`]
+//! - arithmetic functions: [`arith`]
+//! - integer roots: [`roots`]
+//! - number and range expressions: [`expr`]
+//!
+//! ```
+//! use prime_factorization::{Prime, collect_primes, factor};
+//!
+//! assert!(97u64.prime());
+//! assert_eq!(vec![2, 3, 5, 7], collect_primes(0, 10));
+//! assert_eq!(vec![(2, 3), (3, 2), (5, 1)], factor(360));
+//! ```
+
+#![warn(missing_docs)]
+
+pub mod arith;
+pub mod audit;
+pub mod batch;
+pub mod bigint;
+mod bpsw;
+mod ecm;
+pub mod error;
+pub mod expr;
+pub mod factor;
+pub mod fermat;
+pub mod hart;
+mod montgomery;
+pub mod prime;
+mod ring;
+pub mod roots;
+pub mod semiprime;
+pub mod sieve;
+pub mod siqs;
+mod smooth;
+pub mod squfof;
+
+pub use error::FactorError;
+pub use factor::{Factor, factor};
+pub use prime::{Prime, ProbablePrime};
+
+use rayon::prelude::*;
+
+/// All primes in `[start, end]`, in ascending order.
+///
+/// An empty list if `start > end`.
+pub fn collect_primes(start: u64, end: u64) -> Vec<u64> {
+    // sieving needs every prime up to √end, so narrow ranges
+    // are cheaper to test number by number
+    if end.saturating_sub(start) < 16.max(roots::isqrt(end) / 64) {
+        return (start..=end)
+            .into_par_iter()
+            .filter(|&n| n.prime())
+            .collect();
+    }
+
+    sieve::segmented_sieve(start, end)
+}
+
+/// All primes in `[start, end]` for ranges reaching beyond `u64`, in ascending order.
+///
+/// The part up to `u64::MAX` goes through [`collect_primes`], numbers above are
+/// tested one by one, which is only feasible for narrow ranges.
+pub fn collect_primes_u128(start: u128, end: u128) -> Vec<u128> {
+    if start > end {
+        return Vec::new();
+    }
+
+    let mut primes: Vec<u128> = match u64::try_from(start) {
+        Ok(start) => collect_primes(start, end.min(u64::MAX as u128) as u64)
+            .into_iter()
+            .map(u128::from)
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    let wide_start = start.max(1 << 64);
+    if wide_start <= end {
+        primes.par_extend((wide_start..=end).into_par_iter().filter(|&n| n.prime()));
+    }
+    primes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use semiprime::factorize;
+    use std::collections::HashSet;
+
+    #[test]
+    fn all_prime() {
+        let primes: [u64; 25] = [
+            2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83,
+            89, 97,
+        ];
+        assert!(primes.into_par_iter().all(|x| x.prime()));
+    }
+
+    #[test]
+    fn no_prime() {
+        let non_primes: [u64; 25] = [
+            4, 6, 8, 10, 44, 46, 410, 412, 56, 512, 64, 68, 610, 74, 76, 710, 86, 812, 94, 104,
+            106, 1012, 116, 1112, 1210,
+        ];
+
+        assert!(!non_primes.into_par_iter().all(|x| x.prime()));
+    }
+
+    #[test]
+    fn collect_prime() {
+        let primes: [u64; 25] = [
+            2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83,
+            89, 97,
+        ];
+
+        assert_eq!(Vec::from(primes), collect_primes(0, 100));
+    }
+
+    #[test]
+    fn collect_prime_narrow() {
+        assert_eq!(vec![89, 97], collect_primes(89, 100));
+        assert_eq!(vec![97], collect_primes(97, 97));
+        assert!(collect_primes(100, 90).is_empty());
+    }
+
+    #[test]
+    fn collect_prime_boundaries() {
+        assert!(collect_primes(0, 0).is_empty());
+        assert!(collect_primes(0, 1).is_empty());
+        assert!(collect_primes(1, 1).is_empty());
+        assert_eq!(vec![2], collect_primes(0, 2));
+        assert_eq!(vec![2], collect_primes(2, 2));
+        assert_eq!(vec![2, 3], collect_primes(1, 3));
+        assert!(collect_primes(2, 1).is_empty());
+
+        // start..=end must not wrap around at the top
+        assert!(collect_primes(u64::MAX, u64::MAX).is_empty());
+        assert_eq!(vec![u64::MAX - 58], collect_primes(u64::MAX - 58, u64::MAX));
+        assert_eq!(
+            vec![u64::MAX - 94, u64::MAX - 82, u64::MAX - 58],
+            collect_primes(u64::MAX - 100, u64::MAX)
+        );
+        assert!(collect_primes(u64::MAX, 0).is_empty());
+    }
+
+    #[test]
+    fn collect_prime_wide() {
+        assert_eq!(vec![2, 3, 5, 7], collect_primes_u128(0, 10));
+        assert!(collect_primes_u128(10, 0).is_empty());
+
+        // across 2^64, 2^64 - 59 below and 2^64 + 13 above
+        let below = u64::MAX as u128 - 58;
+        let above = (1 << 64) + 13;
+        assert_eq!(
+            vec![below, above],
+            collect_primes_u128(u64::MAX as u128 - 60, (1 << 64) + 20)
+        );
+        assert_eq!(vec![above], collect_primes_u128(1 << 64, (1 << 64) + 13));
+        assert_eq!(
+            vec![u128::MAX - 158],
+            collect_primes_u128(u128::MAX - 160, u128::MAX)
+        );
+    }
+
+    #[test]
+    fn factorized() {
+        let primes: Vec<u64> = vec![2, 3, 5, 7];
+        let factors: HashSet<(u128, u64, u64)> = HashSet::from([
+            (6, 2, 3),
+            (10, 2, 5),
+            (14, 2, 7),
+            (15, 3, 5),
+            (21, 3, 7),
+            (35, 5, 7),
+        ]);
+
+        assert_eq!(factors, factorize(primes));
+    }
+
+    #[test]
+    fn factorized_above_u32() {
+        // 2^32 - 5 lies below 2^32, 2^32 + 15 and 2^32 + 61 above
+        let primes = collect_primes(4_294_967_290, 4_294_967_360);
+        assert_eq!(vec![4_294_967_291, 4_294_967_311, 4_294_967_357], primes);
+
+        let factors = factorize(primes.clone());
+        assert_eq!(primes.len() * (primes.len() - 1) / 2, factors.len());
+        assert!(factors.contains(&(18_446_744_116_659_224_501, 4_294_967_291, 4_294_967_311)));
+        assert!(
+            factors
+                .iter()
+                .all(|&(n, p, q)| n == p as u128 * q as u128 && n > u32::MAX as u128)
+        );
+    }
+
+    #[test]
+    fn factorized_near_u64_max() {
+        // the product wraps around in u64 arithmetic
+        let primes = vec![u64::MAX - 82, u64::MAX - 58];
+        let n = (u64::MAX - 82) as u128 * (u64::MAX - 58) as u128;
+        assert_eq!(
+            HashSet::from([(n, u64::MAX - 82, u64::MAX - 58)]),
+            factorize(primes)
+        );
+    }
+}
@@ -1,165 +1,384 @@
-// TODO optimize memory usage
+mod cli;
+mod output;
+
+use cli::{Cli, Command, Number};
+use output::{Metadata, Target, Verdict};
+use prime_factorization::{
+    FactorError, audit::audit, batch, bigint::BigUint, collect_primes, expr, factor::Pipeline,
+    semiprime,
+};
 use rayon::prelude::*;
-use std::{collections::HashSet, io, process};
+use std::{
+    env, fs,
+    io::{self, Read, Write},
+    path::Path,
+    process,
+    time::Instant,
+};
+
+enum Input {
+    Number(Number),
+    Range(u64, u64),
+}
 
 fn main() {
-    let inp = read_input();
-    let input = parse_input(inp);
-    let primes = collect_primes(input.0, input.1);
-    let factors: HashSet<(u64, u64, u64)> = factorize(primes);
-    // TODO sort factors (glidesort?)
-
-    println!("{:?}", factors.len());
-
-    // TODO bottleneck -> use BufWriter
-    // for factor in factors {
-    //     println!("{:?}", factor);
-    // }
+    if let Err(err) = run() {
+        eprintln!("{err}");
+        if matches!(err, FactorError::StartAfterEnd { .. }) {
+            eprintln!("pass '--swap' to use the range the other way round");
+        }
+        if !matches!(err, FactorError::Io(_)) {
+            eprintln!("try '--help' for more information");
+        }
+        process::exit(exit_code(&err));
+    }
 }
 
-fn read_input() -> String {
-    println!("Enter range [u64 u64]:");
+// every kind of failure gets its own exit code
+fn exit_code(err: &FactorError) -> i32 {
+    match err {
+        FactorError::Io(_) => 1,
+        FactorError::Usage(_) => 2,
+        FactorError::WrongArity { .. } => 3,
+        FactorError::Parse { .. } => 4,
+        FactorError::Overflow { .. } => 5,
+        FactorError::StartAfterEnd { .. } => 6,
+        FactorError::RangeTooLarge { .. } => 7,
+    }
+}
 
-    let mut inp = String::new();
-    io::stdin()
-        .read_line(&mut inp)
-        .expect("Unable to read input");
+fn run() -> Result<(), FactorError> {
+    let started = Instant::now();
+    let args: Vec<String> = env::args().skip(1).collect();
+    let Cli {
+        command,
+        format,
+        output,
+        count,
+        swap,
+        verbose,
+        digits,
+        mut warnings,
+    } = cli::parse_args(&args)?;
+    let target = match &output {
+        Some(path) => Target::File(path),
+        None => Target::Stdout,
+    };
+    let mut meta = Metadata::new(started);
 
-    inp.trim().to_string()
-}
+    // without a command fall back to asking for a range or a number
+    let command = match command {
+        Command::Prompt => match parse_input(read_input()?)? {
+            Input::Number(n) => Command::Factor(vec![n]),
+            Input::Range(start, end) => {
+                let limit = cli::semiprimes_limit(count);
+                let (start, end) =
+                    cli::checked_range("semiprimes", start, end, limit, swap, &mut warnings)?;
+                Command::Semiprimes { start, end }
+            }
+        },
+        command => command,
+    };
+    for warning in &warnings {
+        eprintln!("warning: {warning}");
+    }
 
-fn parse_input(input: String) -> (u64, u64) {
-    // split input to format (u64, u64)
-    let split_input: Vec<&str> = input.split_whitespace().collect();
+    match command {
+        Command::Help => println!("{}", cli::USAGE),
+        Command::Version => println!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")),
+        Command::Factor(numbers) => {
+            let pipeline = Pipeline::for_digits(digits);
+            let factorizations: Vec<(Number, Vec<(Number, u32)>)> = numbers
+                .into_iter()
+                .map(|n| {
+                    let factors = n.factor(&pipeline);
+                    if verbose {
+                        for (p, _, method) in &factors {
+                            eprintln!("{n}: {p} found by {method}");
+                        }
+                    }
+                    let factors = factors.into_iter().map(|(p, exp, _)| (p, exp)).collect();
+                    (n, factors)
+                })
+                .collect();
+            write_to(&target, |out| {
+                output::write_factorizations(&factorizations, format, &meta, out)
+            })?;
+        }
+        Command::IsPrime(numbers) => {
+            let results: Vec<(Number, bool)> = numbers
+                .into_iter()
+                .map(|n| {
+                    let prime = n.prime();
+                    (n, prime)
+                })
+                .collect();
+            write_to(&target, |out| {
+                output::write_primality(&results, format, &meta, out)
+            })?;
+        }
+        Command::Audit(path) => {
+            let moduli = cli::parse_lines(&read_file(&path)?)?;
+            // moduli are independent, and a strong one takes the longest
+            let results: Vec<(Number, Verdict)> = moduli
+                .into_par_iter()
+                .map(|n| {
+                    // a prime has no weakness to look for
+                    let verdict = if n.prime() {
+                        Verdict::Prime
+                    } else {
+                        match audit(&n.to_big()) {
+                            Some(finding) => Verdict::Weak(finding),
+                            None => Verdict::NotFound,
+                        }
+                    };
+                    (n, verdict)
+                })
+                .collect();
+            if verbose {
+                let weak = results
+                    .iter()
+                    .filter(|(_, verdict)| *verdict != Verdict::NotFound)
+                    .count();
+                eprintln!("{weak} of {} moduli are weak", results.len());
+            }
+            write_to(&target, |out| {
+                output::write_audit(&results, format, &meta, out)
+            })?;
+        }
+        Command::BatchGcd(path) => {
+            let moduli: Vec<BigUint> = cli::parse_lines(&read_file(&path)?)?
+                .iter()
+                .map(Number::to_big)
+                .collect();
+            // 0 would zero the whole product, 1 has no prime to share
+            if moduli.iter().any(|n| *n <= BigUint::one()) {
+                eprintln!("warning: skipping moduli 0 and 1");
+            }
+            let moduli: Vec<BigUint> = moduli.into_iter().filter(|n| *n > BigUint::one()).collect();
+            let factored = batch::shared_factors(&moduli);
+            if verbose {
+                eprintln!(
+                    "{} of {} moduli share a prime",
+                    factored.len(),
+                    moduli.len()
+                );
+            }
+            write_to(&target, |out| {
+                output::write_factored(
+                    factored
+                        .into_iter()
+                        .map(|(i, p, q)| [moduli[i].clone(), p, q]),
+                    format,
+                    &meta,
+                    out,
+                )
+            })?;
+        }
+        Command::Primes { start, end } => {
+            let primes = collect_primes(start, end);
+            meta.range = Some((start, end));
+            meta.prime_count = Some(primes.len());
+            write_to(&target, |out| {
+                output::write_primes(&primes, format, &meta, out)
+            })?;
+        }
+        Command::Semiprimes { start, end } => {
+            let primes = collect_primes(start, end);
+
+            // count from the prime list alone, without generating a single product. only
+            // products of two primes of the range count, as in the listing, so neither
+            // squares nor products with a prime below start
+            if count {
+                meta.range = Some((start, end));
+                meta.prime_count = Some(primes.len());
+                let pairs = semiprime::pair_count(&primes);
+                let products = semiprime::semiprime_count(&primes, end as u128);
+                write_to(&target, |out| {
+                    output::write_counts(pairs, products, end, format, &meta, out)
+                })?;
+                return Ok(());
+            }
 
-    if split_input.len() != 2 {
-        eprintln!("2 inputs needed: 'start' and 'end'");
-        process::exit(1);
+            // the pairs are sorted one window of products at a time and streamed into a
+            // buffered writer, a set of all pairs does not fit into memory
+            meta.range = Some((start, end));
+            meta.prime_count = Some(primes.len());
+            meta.pair_count = Some(semiprime::pair_count(&primes));
+            write_to(&target, |out| {
+                output::write_triples(
+                    semiprime::par_sorted_semiprimes(&primes),
+                    format,
+                    &meta,
+                    out,
+                )
+            })?;
+        }
+        Command::Prompt => unreachable!("the prompt was answered above"),
     }
 
-    let parsed_input: Vec<u64> = split_input
-        .iter()
-        .map(|i| {
-            i.parse::<u64>().unwrap_or_else(|err| {
-                eprintln!("{err}");
-                process::exit(1);
-            })
-        })
-        .collect();
-
-    let tuple_input = (parsed_input[0], parsed_input[1]);
-    tuple_input
+    Ok(())
 }
 
-trait Prime {
-    fn prime(self) -> bool;
+fn write_to(
+    target: &Target,
+    write: impl FnOnce(Box<dyn Write>) -> io::Result<()>,
+) -> Result<(), FactorError> {
+    Ok(target.writer().and_then(write)?)
 }
 
-impl Prime for u64 {
-    // check if number is prime
-    fn prime(self) -> bool {
-        // base cases
-        if self < 2 {
-            return false;
-        }
-        if self == 2 || self == 3 {
-            return true;
-        }
-        if self % 2 == 0 || self % 3 == 0 {
-            return false;
-        }
-
-        // if a number n is not prime, it must have at least one pair of factors:
-        // n=a×b, where a and b are factors of n
-        // if both a and b were greater than √n, their product would be greater than n, which is a contradiction
-        // so, at least one of the factors must be ≤ √n
-        // if we don’t find any factors up to √n, there can’t be any beyond it (since they would be paired with a factor already checked)
-        // checking all numbers up to n-1, is O(n) time complexity
-        // stopping at √n reduces it to O(√n)
-        let limit = (self as f64).sqrt() as u64;
-
-        (5..=limit)
-            .step_by(6) // all primes >3 are of the form 6k ± 1
-            .collect::<Vec<u64>>()
-            .par_iter()
-            .all(|&i| self % i != 0 && self % (i + 2) != 0)
+// '-' is stdin
+fn read_file(path: &Path) -> io::Result<String> {
+    if path == Path::new("-") {
+        let mut text = String::new();
+        io::stdin().read_to_string(&mut text)?;
+        return Ok(text);
     }
+    fs::read_to_string(path)
 }
 
-fn collect_primes(start: u64, end: u64) -> Vec<u64> {
-    // filter out non prime numbers
-    (start..=end)
-        .into_par_iter()
-        .filter(|&n| n.prime())
-        .collect()
+fn read_input() -> io::Result<String> {
+    // prompt on stderr, stdout only carries the results
+    eprintln!("Enter range [u64 u64 | a..b | a..=b] or number [u64]:");
+
+    let mut inp = String::new();
+    io::stdin().read_line(&mut inp)?;
+
+    Ok(inp.trim().to_string())
 }
 
-fn factorize(primes: Vec<u64>) -> HashSet<(u64, u64, u64)> {
-    // calculate all prime factors
-    primes
-        .par_iter()
-        .flat_map(|&num1| {
-            primes
-                .par_iter()
-                .filter_map(|&num2| {
-                    // only store (a * b, min(a, b), max(a, b)) to avoid redundant pairs like (6,2,3) and (6,3,2)
-                    // this ensures each unique pair appears only once.
-                    if num1 < num2 {
-                        Some((num1 * num2, num1, num2))
-                    } else {
-                        None
-                    }
-                })
-                .collect::<Vec<_>>()
-        })
-        .collect()
+fn parse_input(input: String) -> Result<Input, FactorError> {
+    // split input to format (u64, u64) or a single u64 to factorize
+    let split_input: Vec<&str> = input.split_whitespace().collect();
+
+    // a single 'a..b' or 'a..=b' is a range, a single 'a+n' is a number
+    if let [range] = split_input[..]
+        && expr::is_range(range)
+    {
+        let (start, end) = expr::parse_range(range)?;
+        return Ok(Input::Range(start, end));
+    }
+
+    if split_input.is_empty() || split_input.len() > 2 {
+        return Err(FactorError::WrongArity {
+            command: "prompt".to_string(),
+            expected: "1 or 2 inputs: 'number' or 'start' and 'end'",
+            found: split_input.len(),
+        });
+    }
+
+    // a single number may be of any size, ranges stay within u64
+    Ok(match split_input[..] {
+        [n] => Input::Number(cli::parse_wide(n)?),
+        _ => Input::Range(
+            cli::parse_number(split_input[0])?,
+            cli::parse_number(split_input[1])?,
+        ),
+    })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use prime_factorization::factor;
 
-    #[test]
-    fn all_prime() {
-        let primes: [u64; 25] = [
-            2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83,
-            89, 97,
-        ];
-        assert!(primes.into_par_iter().all(|x| x.prime()));
+    fn factor_wide(n: &Number) -> Vec<(Number, u32)> {
+        n.factor(&Pipeline::default())
+            .into_iter()
+            .map(|(p, exp, _)| (p, exp))
+            .collect()
     }
 
     #[test]
-    fn no_prime() {
-        let non_primes: [u64; 25] = [
-            4, 6, 8, 10, 44, 46, 410, 412, 56, 512, 64, 68, 610, 74, 76, 710, 86, 812, 94, 104,
-            106, 1012, 116, 1112, 1210,
-        ];
+    fn parsed_number() {
+        assert!(matches!(
+            parse_input("360".to_string()),
+            Ok(Input::Number(Number::U64(360)))
+        ));
+        assert!(matches!(
+            parse_input("10 20".to_string()),
+            Ok(Input::Range(10, 20))
+        ));
+        assert!(matches!(
+            parse_input("1e3 2^10".to_string()),
+            Ok(Input::Range(1000, 1024))
+        ));
+        assert!(matches!(
+            parse_input("10..=20".to_string()),
+            Ok(Input::Range(10, 20))
+        ));
+        assert!(matches!(
+            parse_input("2^61-1".to_string()),
+            Ok(Input::Number(Number::U64(2_305_843_009_213_693_951)))
+        ));
+    }
 
-        assert!(!non_primes.into_par_iter().all(|x| x.prime()));
+    #[test]
+    fn rejected_input() {
+        assert!(matches!(
+            parse_input("".to_string()),
+            Err(FactorError::WrongArity { found: 0, .. })
+        ));
+        assert!(matches!(
+            parse_input("1 2 3".to_string()),
+            Err(FactorError::WrongArity { found: 3, .. })
+        ));
+        assert!(matches!(
+            parse_input("1 two".to_string()),
+            Err(FactorError::Parse { token, .. }) if token == "two"
+        ));
+        assert!(matches!(
+            parse_input("1 99999999999999999999".to_string()),
+            Err(FactorError::Overflow { .. })
+        ));
     }
 
     #[test]
-    fn collect_prime() {
-        let primes: [u64; 25] = [
-            2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83,
-            89, 97,
+    fn distinct_exit_codes() {
+        let errors = [
+            FactorError::Io(io::Error::other("io")),
+            FactorError::Usage(String::new()),
+            FactorError::WrongArity {
+                command: String::new(),
+                expected: "",
+                found: 0,
+            },
+            FactorError::Parse {
+                token: String::new(),
+                reason: String::new(),
+                span: None,
+            },
+            FactorError::Overflow {
+                token: String::new(),
+                span: None,
+            },
+            FactorError::StartAfterEnd { start: 1, end: 0 },
+            FactorError::RangeTooLarge {
+                mode: "",
+                start: 0,
+                end: 0,
+                limit: 0,
+            },
         ];
-
-        assert_eq!(Vec::from(primes), collect_primes(0, 100));
+        let mut codes: Vec<i32> = errors.iter().map(exit_code).collect();
+        codes.sort_unstable();
+        codes.dedup();
+        assert_eq!(errors.len(), codes.len());
+        assert!(codes.iter().all(|&code| code != 0));
     }
 
     #[test]
-    fn factorized() {
-        let primes: Vec<u64> = vec![2, 3, 5, 7];
-        let factors: HashSet<(u64, u64, u64)> = HashSet::from([
-            (6, 2, 3),
-            (10, 2, 5),
-            (14, 2, 7),
-            (15, 3, 5),
-            (21, 3, 7),
-            (35, 5, 7),
-        ]);
-
-        assert_eq!(HashSet::from(factors), factorize(primes));
+    fn formatted_factorization() {
+        assert_eq!(
+            "360 = 2^3 * 3^2 * 5",
+            output::format_factorization(360, &factor(360))
+        );
+        assert_eq!("1 = 1", output::format_factorization(1, &factor(1)));
+
+        let n = cli::parse_wide("2^128 * 9").unwrap();
+        assert_eq!(
+            "3062541302288446171170371466885913903104 = 2^128 * 3^2",
+            output::format_factorization(&n, &factor_wide(&n))
+        );
     }
 }
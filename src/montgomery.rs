@@ -0,0 +1,365 @@
+//! Montgomery multiplication modulo an odd `u128` or [`BigUint`].
+//!
+//! `a * b mod n` for a 128 bit `n` needs a 256 bit product and a slow 256 by 128 bit
+//! division. In Montgomery form `a` is stored as `a * R mod n` with `R = 2^128`, and
+//! the division by `n` becomes a multiplication and a shift. [`BigMontgomery`] does
+//! the same with `R = 2^(64k)` for a `k` limb modulus.
+
+use crate::bigint::BigUint;
+
+/// Arithmetic modulo an odd `n > 1`, on numbers in Montgomery form.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Montgomery {
+    n: u128,
+    // -n^-1 mod R
+    n_neg_inv: u128,
+    // R^2 mod n, converts into Montgomery form
+    r2: u128,
+    // R mod n, the 1 in Montgomery form
+    one: u128,
+}
+
+impl Montgomery {
+    pub(crate) fn new(n: u128) -> Self {
+        assert!(
+            n > 1 && n % 2 == 1,
+            "montgomery form needs an odd modulus > 1"
+        );
+
+        // newton's iteration doubles the correct low bits, n * n = 1 mod 8 gives 3
+        let mut inv = n;
+        for _ in 0..6 {
+            inv = inv.wrapping_mul(2u128.wrapping_sub(n.wrapping_mul(inv)));
+        }
+
+        // R mod n = (R - 1) mod n + 1, then R^2 mod n by doubling R mod n 128 times
+        let one = (u128::MAX % n + 1) % n;
+        let mut r2 = one;
+        for _ in 0..128 {
+            r2 = add_mod(r2, r2, n);
+        }
+
+        Montgomery {
+            n,
+            n_neg_inv: inv.wrapping_neg(),
+            r2,
+            one,
+        }
+    }
+
+    pub(crate) fn modulus(&self) -> u128 {
+        self.n
+    }
+
+    pub(crate) fn one(&self) -> u128 {
+        self.one
+    }
+
+    // a * R mod n
+    pub(crate) fn enter(&self, a: u128) -> u128 {
+        self.mul(a % self.n, self.r2)
+    }
+
+    // back from a * R mod n to a
+    #[cfg(test)]
+    pub(crate) fn leave(&self, a: u128) -> u128 {
+        self.redc(0, a)
+    }
+
+    pub(crate) fn add(&self, a: u128, b: u128) -> u128 {
+        add_mod(a, b, self.n)
+    }
+
+    pub(crate) fn sub(&self, a: u128, b: u128) -> u128 {
+        if a >= b { a - b } else { a + (self.n - b) }
+    }
+
+    // a / 2 mod n, (a + n) / 2 for odd a without overflowing
+    pub(crate) fn half(&self, a: u128) -> u128 {
+        if a & 1 == 0 {
+            a >> 1
+        } else {
+            (a >> 1) + (self.n >> 1) + 1
+        }
+    }
+
+    pub(crate) fn mul(&self, a: u128, b: u128) -> u128 {
+        let (hi, lo) = mul_wide(a, b);
+        self.redc(hi, lo)
+    }
+
+    pub(crate) fn pow(&self, mut base: u128, mut exp: u128) -> u128 {
+        let mut result = self.one;
+        while exp > 0 {
+            if exp & 1 == 1 {
+                result = self.mul(result, base);
+            }
+            base = self.mul(base, base);
+            exp >>= 1;
+        }
+        result
+    }
+
+    // t * R^-1 mod n for t = hi * R + lo < n * R
+    fn redc(&self, hi: u128, lo: u128) -> u128 {
+        // m * n cancels the low half of t, so t + m * n is divisible by R
+        let m = lo.wrapping_mul(self.n_neg_inv);
+        let (mn_hi, mn_lo) = mul_wide(m, self.n);
+        let carry = lo.overflowing_add(mn_lo).1 as u128;
+
+        // the quotient is below 2n, which can exceed 2^128
+        let (t, over1) = hi.overflowing_add(mn_hi);
+        let (t, over2) = t.overflowing_add(carry);
+        if over1 || over2 || t >= self.n {
+            t.wrapping_sub(self.n)
+        } else {
+            t
+        }
+    }
+}
+
+/// Arithmetic modulo an odd [`BigUint`] `n > 1`, on numbers in Montgomery form.
+#[derive(Clone, Debug)]
+pub(crate) struct BigMontgomery {
+    n: BigUint,
+    // -n^-1 mod 2^64, reduction works one limb at a time
+    n_neg_inv: u64,
+    // R^2 mod n
+    r2: BigUint,
+}
+
+impl BigMontgomery {
+    pub(crate) fn new(n: &BigUint) -> Self {
+        assert!(
+            !n.is_even() && !n.is_one(),
+            "montgomery form needs an odd modulus > 1"
+        );
+
+        let low = n.limbs()[0];
+        let mut inv = low;
+        for _ in 0..5 {
+            inv = inv.wrapping_mul(2u64.wrapping_sub(low.wrapping_mul(inv)));
+        }
+
+        let bits = 64 * n.limbs().len() as u32;
+        BigMontgomery {
+            n: n.clone(),
+            n_neg_inv: inv.wrapping_neg(),
+            r2: &(&BigUint::one() << (2 * bits)) % n,
+        }
+    }
+
+    pub(crate) fn modulus(&self) -> &BigUint {
+        &self.n
+    }
+
+    pub(crate) fn enter(&self, a: &BigUint) -> BigUint {
+        self.mul(&(a % &self.n), &self.r2)
+    }
+
+    #[cfg(test)]
+    pub(crate) fn leave(&self, a: &BigUint) -> BigUint {
+        self.mul(a, &BigUint::one())
+    }
+
+    pub(crate) fn add(&self, a: &BigUint, b: &BigUint) -> BigUint {
+        let sum = a + b;
+        if sum >= self.n { &sum - &self.n } else { sum }
+    }
+
+    pub(crate) fn sub(&self, a: &BigUint, b: &BigUint) -> BigUint {
+        if a >= b { a - b } else { &(a + &self.n) - b }
+    }
+
+    pub(crate) fn half(&self, a: &BigUint) -> BigUint {
+        if a.is_even() {
+            a >> 1
+        } else {
+            &(a + &self.n) >> 1
+        }
+    }
+
+    // a * b * R^-1 mod n, interleaving the product with the reduction limb by limb
+    pub(crate) fn mul(&self, a: &BigUint, b: &BigUint) -> BigUint {
+        let n = self.n.limbs();
+        let k = n.len();
+        let (a, b) = (a.limbs(), b.limbs());
+        let mut t = vec![0u64; k + 2];
+
+        for i in 0..k {
+            let bi = b.get(i).copied().unwrap_or(0) as u128;
+            // t += a * b[i]
+            let mut carry = 0u128;
+            for (j, &aj) in a.iter().enumerate() {
+                let sum = t[j] as u128 + aj as u128 * bi + carry;
+                t[j] = sum as u64;
+                carry = sum >> 64;
+            }
+            for tj in &mut t[a.len()..] {
+                if carry == 0 {
+                    break;
+                }
+                let sum = *tj as u128 + carry;
+                *tj = sum as u64;
+                carry = sum >> 64;
+            }
+
+            // t += m * n clears the lowest limb, which is shifted out
+            let m = t[0].wrapping_mul(self.n_neg_inv) as u128;
+            let mut carry = (t[0] as u128 + m * n[0] as u128) >> 64;
+            for j in 1..k {
+                let sum = t[j] as u128 + m * n[j] as u128 + carry;
+                t[j - 1] = sum as u64;
+                carry = sum >> 64;
+            }
+            let sum = t[k] as u128 + carry;
+            t[k - 1] = sum as u64;
+            t[k] = t[k + 1] + (sum >> 64) as u64;
+            t[k + 1] = 0;
+        }
+
+        // the result is below 2n
+        let t = BigUint::from_limbs(t);
+        if t >= self.n { &t - &self.n } else { t }
+    }
+}
+
+// a + b mod n for a, b < n, without overflowing for n close to 2^128
+fn add_mod(a: u128, b: u128, n: u128) -> u128 {
+    let (sum, over) = a.overflowing_add(b);
+    if over || sum >= n {
+        sum.wrapping_sub(n)
+    } else {
+        sum
+    }
+}
+
+// full 256 bit product as (high, low) halves
+fn mul_wide(a: u128, b: u128) -> (u128, u128) {
+    const LOW: u128 = u64::MAX as u128;
+    let (a1, a0) = (a >> 64, a & LOW);
+    let (b1, b0) = (b >> 64, b & LOW);
+
+    let low = a0 * b0;
+    let cross1 = a0 * b1;
+    let cross2 = a1 * b0;
+    let high = a1 * b1;
+
+    // the middle column collects at most three 64 bit numbers
+    let mid = (low >> 64) + (cross1 & LOW) + (cross2 & LOW);
+    (
+        high + (cross1 >> 64) + (cross2 >> 64) + (mid >> 64),
+        (low & LOW) | (mid << 64),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a * b mod n by shifting and adding, slow but obviously right
+    fn mul_mod_slow(a: u128, mut b: u128, n: u128) -> u128 {
+        let mut a = a % n;
+        let mut result = 0;
+        while b > 0 {
+            if b & 1 == 1 {
+                result = add_mod(result, a, n);
+            }
+            a = add_mod(a, a, n);
+            b >>= 1;
+        }
+        result
+    }
+
+    #[test]
+    fn big_montgomery() {
+        let moduli = [
+            BigUint::from(101u64),
+            BigUint::from(u64::MAX),
+            BigUint::from(u128::MAX),
+            &(&BigUint::one() << 521) - &BigUint::one(),
+            &(&BigUint::one() << 300) + &BigUint::from(157u64),
+        ];
+        for n in &moduli {
+            let mont = BigMontgomery::new(n);
+            let values = [
+                BigUint::zero(),
+                BigUint::one(),
+                &BigUint::from(12_345u64) % n,
+                n / &BigUint::from(3u64),
+                n - &BigUint::one(),
+            ];
+            for a in &values {
+                assert_eq!(*a, mont.leave(&mont.enter(a)));
+                for b in &values {
+                    let product = mont.leave(&mont.mul(&mont.enter(a), &mont.enter(b)));
+                    assert_eq!(&(a * b) % n, product, "{a} * {b} mod {n}");
+                    let diff = mont.leave(&mont.sub(&mont.enter(a), &mont.enter(b)));
+                    assert_eq!(&(&(a + n) - b) % n, diff);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn wide_products() {
+        assert_eq!((0, 6), mul_wide(2, 3));
+        assert_eq!((0, 1 << 127), mul_wide(1 << 64, 1 << 63));
+        assert_eq!((1, 0), mul_wide(1 << 64, 1 << 64));
+        // (2^128 - 1)^2 = 2^256 - 2^129 + 1
+        assert_eq!((u128::MAX - 1, 1), mul_wide(u128::MAX, u128::MAX));
+    }
+
+    #[test]
+    fn montgomery_round_trip() {
+        for n in [
+            3,
+            101,
+            (1 << 61) - 1,
+            (1 << 89) - 1,
+            u128::MAX,
+            u128::MAX - 2,
+        ] {
+            let mont = Montgomery::new(n);
+            assert_eq!(1 % n, mont.leave(mont.one()));
+            for a in [0, 1, 2, n / 3, n - 1] {
+                assert_eq!(a, mont.leave(mont.enter(a)));
+            }
+        }
+    }
+
+    #[test]
+    fn montgomery_products() {
+        for n in [
+            101,
+            (1 << 61) - 1,
+            (1 << 127) - 1,
+            u128::MAX,
+            u128::MAX - 158,
+        ] {
+            let mont = Montgomery::new(n);
+            for (a, b) in [(2, 3), (n - 1, n - 1), (n / 2, n / 3), (1 << 100, 12345)] {
+                let (a, b) = (a % n, b % n);
+                let product = mont.mul(mont.enter(a), mont.enter(b));
+                assert_eq!(mul_mod_slow(a, b, n), mont.leave(product));
+                let sum = mont.add(mont.enter(a), mont.enter(b));
+                assert_eq!(add_mod(a, b, n), mont.leave(sum));
+                let half = mont.half(mont.enter(a));
+                assert_eq!(a, mul_mod_slow(mont.leave(half), 2, n));
+                let diff = mont.sub(mont.enter(a), mont.enter(b));
+                assert_eq!(add_mod(a, n - b, n) % n, mont.leave(diff));
+            }
+        }
+    }
+
+    #[test]
+    fn montgomery_powers() {
+        // fermat's little theorem for the mersenne prime 2^127 - 1
+        let p = (1 << 127) - 1;
+        let mont = Montgomery::new(p);
+        assert_eq!(1, mont.leave(mont.pow(mont.enter(3), p - 1)));
+        // 2^127 = 1 mod 2^127 - 1
+        assert_eq!(1, mont.leave(mont.pow(mont.enter(2), 127)));
+        assert_eq!(1, mont.leave(mont.pow(mont.enter(5), 0)));
+    }
+}
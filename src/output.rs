@@ -0,0 +1,657 @@
+use prime_factorization::{FactorError, audit::Finding};
+use std::{
+    fmt::Display,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+    str::FromStr,
+    time::Instant,
+};
+
+// where results are written to, stdout unless a file was given
+pub enum Target<'a> {
+    Stdout,
+    File(&'a Path),
+}
+
+impl Target<'_> {
+    pub fn writer(&self) -> io::Result<Box<dyn Write>> {
+        Ok(match self {
+            // the lock is held for the whole run instead of once per line
+            Target::Stdout => Box::new(io::stdout().lock()),
+            Target::File(path) => Box::new(File::create(path)?),
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Format {
+    #[default]
+    Plain,
+    // a single document with metadata
+    Json,
+    // one object per line, can be consumed while it is written
+    Ndjson,
+    Csv,
+    Tsv,
+}
+
+impl FromStr for Format {
+    type Err = FactorError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "plain" => Ok(Format::Plain),
+            "json" => Ok(Format::Json),
+            "ndjson" => Ok(Format::Ndjson),
+            "csv" => Ok(Format::Csv),
+            "tsv" => Ok(Format::Tsv),
+            _ => Err(FactorError::Parse {
+                token: s.to_string(),
+                reason: "expected one of: plain, json, ndjson, csv, tsv".to_string(),
+                span: None,
+            }),
+        }
+    }
+}
+
+// what the json document reports besides the results
+pub struct Metadata {
+    pub range: Option<(u64, u64)>,
+    pub prime_count: Option<usize>,
+    pub pair_count: Option<u128>,
+    pub started: Instant,
+}
+
+impl Metadata {
+    pub fn new(started: Instant) -> Self {
+        Metadata {
+            range: None,
+            prime_count: None,
+            pair_count: None,
+            started,
+        }
+    }
+
+    // the leading fields of the json document, everything known before the results
+    fn write_head(&self, out: &mut impl Write) -> io::Result<()> {
+        write!(out, "{{")?;
+        if let Some((start, end)) = self.range {
+            write!(out, "\"range\":{{\"start\":{start},\"end\":{end}}},")?;
+        }
+        if let Some(count) = self.prime_count {
+            write!(out, "\"prime_count\":{count},")?;
+        }
+        if let Some(count) = self.pair_count {
+            write!(out, "\"pair_count\":{count},")?;
+        }
+        Ok(())
+    }
+
+    // the timing is only known once all results are written, so it closes the document
+    fn write_tail(&self, out: &mut impl Write) -> io::Result<()> {
+        let elapsed = self.started.elapsed().as_secs_f64();
+        writeln!(out, "],\"elapsed_seconds\":{elapsed:.6}}}")
+    }
+}
+
+// one prime per record
+pub fn write_primes<W: Write>(
+    primes: &[u64],
+    format: Format,
+    meta: &Metadata,
+    out: W,
+) -> io::Result<()> {
+    write_records(
+        primes.iter().map(|&p| [p as u128]),
+        ["prime"],
+        "primes",
+        format,
+        meta,
+        |[p]| p.to_string(),
+        out,
+    )
+}
+
+// one (p * q, p, q) per record
+pub fn write_triples<W: Write>(
+    triples: impl Iterator<Item = (u128, u64, u64)>,
+    format: Format,
+    meta: &Metadata,
+    out: W,
+) -> io::Result<()> {
+    write_records(
+        triples.map(|(n, p, q)| [n, p as u128, q as u128]),
+        ["product", "p", "q"],
+        "triples",
+        format,
+        meta,
+        |&[n, p, q]| format!("{:?}", (n, p as u64, q as u64)),
+        out,
+    )
+}
+
+// the counts of the semiprimes of a range as a single record, all pairs p < q of its
+// primes and those with p * q <= end
+pub fn write_counts<W: Write>(
+    pairs: u128,
+    products: u128,
+    end: u64,
+    format: Format,
+    meta: &Metadata,
+    out: W,
+) -> io::Result<()> {
+    write_records(
+        [[pairs, products]].into_iter(),
+        ["pairs", "products_up_to_end"],
+        "counts",
+        format,
+        meta,
+        |[pairs, products]| format!("pairs: {pairs}\nproducts <= {end}: {products}"),
+        out,
+    )
+}
+
+// one (modulus, p, q) per record
+pub fn write_factored<W: Write, T: Display>(
+    factored: impl Iterator<Item = [T; 3]>,
+    format: Format,
+    meta: &Metadata,
+    out: W,
+) -> io::Result<()> {
+    write_records(
+        factored,
+        ["modulus", "p", "q"],
+        "factored",
+        format,
+        meta,
+        |[n, p, q]| format!("{n} = {p} * {q}"),
+        out,
+    )
+}
+
+// one number and whether it is prime per record
+pub fn write_primality<W: Write, T: Display>(
+    results: &[(T, bool)],
+    format: Format,
+    meta: &Metadata,
+    out: W,
+) -> io::Result<()> {
+    write_records(
+        results
+            .iter()
+            .map(|(n, prime)| [n.to_string(), prime.to_string()]),
+        ["number", "prime"],
+        "results",
+        format,
+        meta,
+        |[n, prime]| match prime.as_str() {
+            "true" => format!("{n} is prime"),
+            _ => format!("{n} is not prime"),
+        },
+        out,
+    )
+}
+
+// flat records written through a single buffer in the chosen format
+fn write_records<W: Write, T: Display, const N: usize>(
+    records: impl Iterator<Item = [T; N]>,
+    columns: [&str; N],
+    key: &str,
+    format: Format,
+    meta: &Metadata,
+    plain: impl Fn(&[T; N]) -> String,
+    out: W,
+) -> io::Result<()> {
+    let mut out = BufWriter::new(out);
+
+    match format {
+        Format::Plain => {
+            for record in records {
+                writeln!(out, "{}", plain(&record))?;
+            }
+        }
+        Format::Json => {
+            meta.write_head(&mut out)?;
+            write!(out, "\"{key}\":[")?;
+            for (i, record) in records.enumerate() {
+                if i > 0 {
+                    write!(out, ",")?;
+                }
+                write_object(&mut out, &columns, &record)?;
+            }
+            meta.write_tail(&mut out)?;
+        }
+        Format::Ndjson => {
+            for record in records {
+                write_object(&mut out, &columns, &record)?;
+                writeln!(out)?;
+            }
+        }
+        Format::Csv | Format::Tsv => {
+            let separator = separator(format);
+            writeln!(out, "{}", columns.join(separator))?;
+            for record in records {
+                write_separated(&mut out, separator, &record)?;
+            }
+        }
+    }
+
+    out.flush()
+}
+
+// one number and its prime factors per record, csv and tsv use one row per prime factor
+pub fn write_factorizations<W: Write, T: Display>(
+    factorizations: &[(T, Vec<(T, u32)>)],
+    format: Format,
+    meta: &Metadata,
+    out: W,
+) -> io::Result<()> {
+    let mut out = BufWriter::new(out);
+
+    match format {
+        Format::Plain => {
+            for (n, factors) in factorizations {
+                writeln!(out, "{}", format_factorization(n, factors))?;
+            }
+        }
+        Format::Json => {
+            meta.write_head(&mut out)?;
+            write!(out, "\"factorizations\":[")?;
+            for (i, (n, factors)) in factorizations.iter().enumerate() {
+                if i > 0 {
+                    write!(out, ",")?;
+                }
+                write_factorization_object(&mut out, n, factors)?;
+            }
+            meta.write_tail(&mut out)?;
+        }
+        Format::Ndjson => {
+            for (n, factors) in factorizations {
+                write_factorization_object(&mut out, n, factors)?;
+                writeln!(out)?;
+            }
+        }
+        Format::Csv | Format::Tsv => {
+            let separator = separator(format);
+            writeln!(out, "{}", ["number", "prime", "exponent"].join(separator))?;
+            for (n, factors) in factorizations {
+                // 0 and 1 have no prime factors, they still get a row
+                if factors.is_empty() {
+                    writeln!(out, "{n}{separator}{separator}")?;
+                }
+                for (p, exp) in factors {
+                    writeln!(out, "{n}{separator}{p}{separator}{exp}")?;
+                }
+            }
+        }
+    }
+
+    out.flush()
+}
+
+// what the audit of a single modulus found
+#[derive(Debug, PartialEq, Eq)]
+pub enum Verdict {
+    Weak(Finding),
+    Prime,
+    // none of the weaknesses turned up
+    NotFound,
+}
+
+impl Verdict {
+    // the weakness column, a prime is as broken as a modulus can be
+    fn weakness(&self) -> Option<String> {
+        match self {
+            Verdict::Weak(finding) => Some(finding.weakness.to_string()),
+            Verdict::Prime => Some("prime".to_string()),
+            Verdict::NotFound => None,
+        }
+    }
+}
+
+// one modulus per record with its weakness and the factors it gave away, weakness, p
+// and q are left out, null or empty where there are none
+pub fn write_audit<W: Write, T: Display>(
+    results: &[(T, Verdict)],
+    format: Format,
+    meta: &Metadata,
+    out: W,
+) -> io::Result<()> {
+    let mut out = BufWriter::new(out);
+
+    match format {
+        Format::Plain => {
+            for (n, verdict) in results {
+                match verdict {
+                    Verdict::Weak(Finding { weakness, p, q }) => {
+                        writeln!(out, "{n}: {weakness}, {p} * {q}")?
+                    }
+                    Verdict::Prime => writeln!(out, "{n}: prime")?,
+                    Verdict::NotFound => writeln!(out, "{n}: no weakness found")?,
+                }
+            }
+        }
+        Format::Json => {
+            meta.write_head(&mut out)?;
+            write!(out, "\"moduli\":[")?;
+            for (i, (n, verdict)) in results.iter().enumerate() {
+                if i > 0 {
+                    write!(out, ",")?;
+                }
+                write_audit_object(&mut out, n, verdict)?;
+            }
+            meta.write_tail(&mut out)?;
+        }
+        Format::Ndjson => {
+            for (n, verdict) in results {
+                write_audit_object(&mut out, n, verdict)?;
+                writeln!(out)?;
+            }
+        }
+        Format::Csv | Format::Tsv => {
+            let separator = separator(format);
+            writeln!(out, "{}", ["modulus", "weakness", "p", "q"].join(separator))?;
+            for (n, verdict) in results {
+                let weakness = verdict.weakness().unwrap_or_default();
+                match verdict {
+                    Verdict::Weak(Finding { p, q, .. }) => {
+                        write_separated(&mut out, separator, &[n as &dyn Display, &weakness, p, q])?
+                    }
+                    _ => writeln!(out, "{n}{separator}{weakness}{separator}{separator}")?,
+                }
+            }
+        }
+    }
+
+    out.flush()
+}
+
+// 360 = 2^3 * 3^2 * 5
+pub fn format_factorization(n: impl Display, factors: &[(impl Display, u32)]) -> String {
+    if factors.is_empty() {
+        return format!("{n} = {n}");
+    }
+
+    let terms: Vec<String> = factors
+        .iter()
+        .map(|(p, exp)| match exp {
+            1 => p.to_string(),
+            _ => format!("{p}^{exp}"),
+        })
+        .collect();
+    format!("{n} = {}", terms.join(" * "))
+}
+
+fn separator(format: Format) -> &'static str {
+    match format {
+        Format::Tsv => "\t",
+        _ => ",",
+    }
+}
+
+fn write_separated(
+    out: &mut impl Write,
+    separator: &str,
+    values: &[impl Display],
+) -> io::Result<()> {
+    for (i, value) in values.iter().enumerate() {
+        if i > 0 {
+            write!(out, "{separator}")?;
+        }
+        write!(out, "{value}")?;
+    }
+    writeln!(out)
+}
+
+// numbers only, so nothing needs escaping
+fn write_object(out: &mut impl Write, columns: &[&str], values: &[impl Display]) -> io::Result<()> {
+    write!(out, "{{")?;
+    for (i, (column, value)) in columns.iter().zip(values).enumerate() {
+        if i > 0 {
+            write!(out, ",")?;
+        }
+        write!(out, "\"{column}\":{value}")?;
+    }
+    write!(out, "}}")
+}
+
+fn write_factorization_object(
+    out: &mut impl Write,
+    n: impl Display,
+    factors: &[(impl Display, u32)],
+) -> io::Result<()> {
+    write!(out, "{{\"number\":{n},\"factors\":[")?;
+    for (i, (p, exp)) in factors.iter().enumerate() {
+        if i > 0 {
+            write!(out, ",")?;
+        }
+        write!(out, "{{\"prime\":{p},\"exponent\":{exp}}}")?;
+    }
+    write!(out, "]}}")
+}
+
+fn write_audit_object(out: &mut impl Write, n: impl Display, verdict: &Verdict) -> io::Result<()> {
+    write!(out, "{{\"modulus\":{n},\"weakness\":")?;
+    match verdict.weakness() {
+        Some(weakness) => write!(out, "\"{weakness}\"")?,
+        None => write!(out, "null")?,
+    }
+    if let Verdict::Weak(Finding { p, q, .. }) = verdict {
+        write!(out, ",\"p\":{p},\"q\":{q}")?;
+    }
+    write!(out, "}}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prime_factorization::{
+        audit::Weakness, bigint::BigUint, factor, semiprime::sorted_semiprimes,
+    };
+
+    fn written(write: impl FnOnce(&mut Vec<u8>) -> io::Result<()>) -> String {
+        let mut out = Vec::new();
+        write(&mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    // the elapsed time differs between runs
+    fn without_timing(json: &str) -> &str {
+        &json[..json.find("\"elapsed_seconds\"").unwrap()]
+    }
+
+    #[test]
+    fn written_triples() {
+        let meta = Metadata::new(Instant::now());
+        let triples = |format| {
+            written(|out| write_triples(sorted_semiprimes(&[2, 3, 5]), format, &meta, out))
+        };
+
+        assert_eq!(
+            "(6, 2, 3)\n(10, 2, 5)\n(15, 3, 5)\n",
+            triples(Format::Plain)
+        );
+        assert_eq!("product,p,q\n6,2,3\n10,2,5\n15,3,5\n", triples(Format::Csv));
+        assert_eq!(
+            "product\tp\tq\n6\t2\t3\n10\t2\t5\n15\t3\t5\n",
+            triples(Format::Tsv)
+        );
+        assert_eq!(
+            "{\"product\":6,\"p\":2,\"q\":3}\n{\"product\":10,\"p\":2,\"q\":5}\n{\"product\":15,\"p\":3,\"q\":5}\n",
+            triples(Format::Ndjson)
+        );
+    }
+
+    #[test]
+    fn written_json_document() {
+        let meta = Metadata {
+            range: Some((0, 5)),
+            prime_count: Some(3),
+            pair_count: Some(3),
+            started: Instant::now(),
+        };
+        let json =
+            written(|out| write_triples(sorted_semiprimes(&[2, 3, 5]), Format::Json, &meta, out));
+
+        assert_eq!(
+            "{\"range\":{\"start\":0,\"end\":5},\"prime_count\":3,\"pair_count\":3,\"triples\":[{\"product\":6,\"p\":2,\"q\":3},{\"product\":10,\"p\":2,\"q\":5},{\"product\":15,\"p\":3,\"q\":5}],",
+            without_timing(&json)
+        );
+        assert!(json.ends_with("}\n"));
+
+        let empty =
+            written(|out| write_primes(&[], Format::Json, &Metadata::new(Instant::now()), out));
+        assert_eq!("{\"primes\":[],", without_timing(&empty));
+    }
+
+    #[test]
+    fn written_primes() {
+        let meta = Metadata::new(Instant::now());
+        let primes = |format| written(|out| write_primes(&[2, 3], format, &meta, out));
+
+        assert_eq!("2\n3\n", primes(Format::Plain));
+        assert_eq!("prime\n2\n3\n", primes(Format::Csv));
+        assert_eq!("{\"prime\":2}\n{\"prime\":3}\n", primes(Format::Ndjson));
+    }
+
+    #[test]
+    fn written_factorizations() {
+        let meta = Metadata::new(Instant::now());
+        let numbers = [(360, factor(360)), (1, factor(1))];
+        let factorizations =
+            |format| written(|out| write_factorizations(&numbers, format, &meta, out));
+
+        assert_eq!(
+            "360 = 2^3 * 3^2 * 5\n1 = 1\n",
+            factorizations(Format::Plain)
+        );
+        assert_eq!(
+            "number,prime,exponent\n360,2,3\n360,3,2\n360,5,1\n1,,\n",
+            factorizations(Format::Csv)
+        );
+        assert_eq!(
+            "{\"number\":360,\"factors\":[{\"prime\":2,\"exponent\":3},{\"prime\":3,\"exponent\":2},{\"prime\":5,\"exponent\":1}]}\n{\"number\":1,\"factors\":[]}\n",
+            factorizations(Format::Ndjson)
+        );
+        assert!(factorizations(Format::Json).starts_with("{\"factorizations\":[{\"number\":360,"));
+    }
+
+    #[test]
+    fn parsed_format() {
+        assert_eq!(Format::Json, "json".parse().unwrap());
+        assert_eq!(Format::Tsv, "TSV".parse().unwrap());
+        assert!(matches!(
+            "xml".parse::<Format>(),
+            Err(FactorError::Parse { token, .. }) if token == "xml"
+        ));
+    }
+
+    #[test]
+    fn written_primality() {
+        let meta = Metadata::new(Instant::now());
+        let results =
+            |format| written(|out| write_primality(&[(97, true), (91, false)], format, &meta, out));
+
+        assert_eq!("97 is prime\n91 is not prime\n", results(Format::Plain));
+        assert_eq!("number,prime\n97,true\n91,false\n", results(Format::Csv));
+        assert_eq!(
+            "{\"number\":97,\"prime\":true}\n{\"number\":91,\"prime\":false}\n",
+            results(Format::Ndjson)
+        );
+    }
+
+    #[test]
+    fn written_to_file() {
+        let path = std::env::temp_dir().join("prime_factorization_written_to_file.txt");
+        let out = Target::File(&path).writer().unwrap();
+        write_primes(
+            &[2, 3, 5],
+            Format::Plain,
+            &Metadata::new(Instant::now()),
+            out,
+        )
+        .unwrap();
+        assert_eq!("2\n3\n5\n", std::fs::read_to_string(&path).unwrap());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn written_audit() {
+        let meta = Metadata::new(Instant::now());
+        let finding = Finding {
+            weakness: Weakness::ClosePrimes,
+            p: BigUint::from(11u64),
+            q: BigUint::from(13u64),
+        };
+        let results = [
+            (143, Verdict::Weak(finding)),
+            (13, Verdict::Prime),
+            (
+                1_000_000_007 * 18_446_744_073_709_551_557u128,
+                Verdict::NotFound,
+            ),
+        ];
+        let audit = |format| written(|out| write_audit(&results, format, &meta, out));
+
+        assert_eq!(
+            "143: close primes, 11 * 13\n13: prime\n18446744202836760072966860899: no weakness found\n",
+            audit(Format::Plain)
+        );
+        assert_eq!(
+            "modulus,weakness,p,q\n143,close primes,11,13\n13,prime,,\n18446744202836760072966860899,,,\n",
+            audit(Format::Csv)
+        );
+        assert_eq!(
+            "{\"modulus\":143,\"weakness\":\"close primes\",\"p\":11,\"q\":13}\n{\"modulus\":13,\"weakness\":\"prime\"}\n{\"modulus\":18446744202836760072966860899,\"weakness\":null}\n",
+            audit(Format::Ndjson)
+        );
+        assert!(audit(Format::Json).starts_with("{\"moduli\":[{\"modulus\":143,"));
+    }
+
+    #[test]
+    fn written_factored() {
+        let meta = Metadata::new(Instant::now());
+        let factored = |format| {
+            written(|out| {
+                write_factored(
+                    [[143, 11, 13], [299, 13, 23]].into_iter(),
+                    format,
+                    &meta,
+                    out,
+                )
+            })
+        };
+
+        assert_eq!("143 = 11 * 13\n299 = 13 * 23\n", factored(Format::Plain));
+        assert_eq!(
+            "modulus\tp\tq\n143\t11\t13\n299\t13\t23\n",
+            factored(Format::Tsv)
+        );
+        assert_eq!(
+            "{\"modulus\":143,\"p\":11,\"q\":13}\n{\"modulus\":299,\"p\":13,\"q\":23}\n",
+            factored(Format::Ndjson)
+        );
+    }
+
+    #[test]
+    fn written_counts() {
+        let meta = Metadata {
+            range: Some((50, 100)),
+            ..Metadata::new(Instant::now())
+        };
+        let counts = |format| written(|out| write_counts(45, 0, 100, format, &meta, out));
+
+        assert_eq!("pairs: 45\nproducts <= 100: 0\n", counts(Format::Plain));
+        assert_eq!("pairs,products_up_to_end\n45,0\n", counts(Format::Csv));
+        assert_eq!("pairs\tproducts_up_to_end\n45\t0\n", counts(Format::Tsv));
+        assert_eq!(
+            "{\"pairs\":45,\"products_up_to_end\":0}\n",
+            counts(Format::Ndjson)
+        );
+        assert_eq!(
+            "{\"range\":{\"start\":50,\"end\":100},\"counts\":[{\"pairs\":45,\"products_up_to_end\":0}],",
+            without_timing(&counts(Format::Json))
+        );
+    }
+}
@@ -0,0 +1,471 @@
+//! Primality testing.
+
+use crate::{
+    arith::{mul_mod, pow_mod},
+    bigint::BigUint,
+    bpsw::{bpsw_big, bpsw_u128},
+    montgomery::Montgomery,
+    roots::isqrt,
+};
+use rayon::prelude::*;
+
+// witnesses that make miller-rabin deterministic for every u64 (jim sinclair)
+const WITNESSES: [u64; 7] = [2, 325, 9375, 28178, 450775, 9780504, 1795265022];
+
+// the first 13 primes are deterministic witnesses below WIDE_BOUND (sorenson and webster),
+// the bound itself is the smallest composite passing them all
+const WIDE_WITNESSES: [u128; 13] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41];
+const WIDE_BOUND: u128 = 3_317_044_064_679_887_385_961_981;
+
+// small primes to weed out most composites before the modular exponentiations
+const SMALL_PRIMES: [u64; 12] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37];
+
+/// Primality test.
+pub trait Prime {
+    /// Returns `true` if the number is prime.
+    fn prime(self) -> bool;
+}
+
+impl Prime for u64 {
+    /// Deterministic Miller-Rabin, exact for every `u64`.
+    ///
+    /// Sequential and allocation-free, so it can run inside parallel iterators.
+    fn prime(self) -> bool {
+        miller_rabin(self)
+    }
+}
+
+impl Prime for u32 {
+    /// Deterministic Miller-Rabin, exact for every `u32`.
+    fn prime(self) -> bool {
+        miller_rabin(self as u64)
+    }
+}
+
+impl Prime for u128 {
+    /// Miller-Rabin in Montgomery form, exact below 3.3 * 10^24.
+    ///
+    /// No finite set of bases is proven above that, so larger numbers get the
+    /// Baillie-PSW test of [`ProbablePrime`]. No composite is known to pass it, but
+    /// that is not a proof.
+    fn prime(self) -> bool {
+        match u64::try_from(self) {
+            Ok(n) => miller_rabin(n),
+            Err(_) if self < WIDE_BOUND => wide_miller_rabin(self),
+            Err(_) => bpsw_u128(self),
+        }
+    }
+}
+
+impl Prime for &BigUint {
+    /// Up to `u128` as for `u128`, above that the Baillie-PSW test of
+    /// [`ProbablePrime`]. No composite is known to pass it, but that is not a proof.
+    /// Miller-Rabin to a fixed set of bases is not enough this far up, composites
+    /// passing every prime base below 307 are known.
+    fn prime(self) -> bool {
+        match self.to_u128() {
+            Some(n) => n.prime(),
+            None => bpsw_big(self),
+        }
+    }
+}
+
+impl Prime for BigUint {
+    fn prime(self) -> bool {
+        (&self).prime()
+    }
+}
+
+/// Baillie-PSW probable-prime test.
+///
+/// A strong probable-prime test to base 2 followed by a strong Lucas test with
+/// Selfridge's parameters. It is exact below 2^64, above that no composite passing it
+/// is known. [`Prime`] uses it for numbers above `u128`.
+///
+/// ```
+/// use prime_factorization::ProbablePrime;
+///
+/// assert!(((1u128 << 127) - 1).probable_prime());
+/// // 2047 = 23 * 89 is a strong pseudoprime to base 2, the lucas test catches it
+/// assert!(!2047u64.probable_prime());
+/// ```
+pub trait ProbablePrime {
+    /// Returns `true` if the number is a probable prime.
+    fn probable_prime(self) -> bool;
+}
+
+impl ProbablePrime for u64 {
+    fn probable_prime(self) -> bool {
+        bpsw_u128(self as u128)
+    }
+}
+
+impl ProbablePrime for u128 {
+    fn probable_prime(self) -> bool {
+        bpsw_u128(self)
+    }
+}
+
+impl ProbablePrime for &BigUint {
+    fn probable_prime(self) -> bool {
+        bpsw_big(self)
+    }
+}
+
+impl ProbablePrime for BigUint {
+    fn probable_prime(self) -> bool {
+        bpsw_big(&self)
+    }
+}
+
+/// Primality by trial division with the candidates 6k ± 1 up to √n, in O(√n).
+///
+/// Sequential and allocation-free, cheap enough for small `n` and safe to call from
+/// inside parallel iterators. [`Prime::prime`] is much faster for large `n`, this is
+/// the reference it is verified against.
+pub fn trial_division(n: u64) -> bool {
+    // base cases
+    if n < 2 {
+        return false;
+    }
+    if n == 2 || n == 3 {
+        return true;
+    }
+    if n.is_multiple_of(2) || n.is_multiple_of(3) {
+        return false;
+    }
+
+    // if a number n is not prime, it must have at least one pair of factors:
+    // n=a×b, where a and b are factors of n
+    // if both a and b were greater than √n, their product would be greater than n, which is a contradiction
+    // so, at least one of the factors must be ≤ √n
+    // if we don’t find any factors up to √n, there can’t be any beyond it (since they would be paired with a factor already checked)
+    // checking all numbers up to n-1, is O(n) time complexity
+    // stopping at √n reduces it to O(√n)
+    no_divisor(n, 1, last_k(n))
+}
+
+/// [`trial_division`] with the candidates split across the rayon thread pool.
+///
+/// Only worth it for a single large `n`, below 2^40 this falls back to the
+/// sequential check. Don't call it from inside parallel iterators over many
+/// numbers, the outer iterator already keeps every thread busy.
+pub fn par_trial_division(n: u64) -> bool {
+    if n < PAR_TRIAL_MIN {
+        return trial_division(n);
+    }
+    if n.is_multiple_of(2) || n.is_multiple_of(3) {
+        return false;
+    }
+
+    let last = last_k(n);
+    (0..last.div_ceil(TRIAL_BLOCK))
+        .into_par_iter()
+        .all(|block| {
+            let first = block * TRIAL_BLOCK + 1;
+            no_divisor(n, first, (first + TRIAL_BLOCK - 1).min(last))
+        })
+}
+
+// smallest n whose √n has enough candidates to pay for the thread pool
+const PAR_TRIAL_MIN: u64 = 1 << 40;
+
+// number of k per parallel task, 2^16 k are 2^17 divisions
+const TRIAL_BLOCK: u64 = 1 << 16;
+
+// largest k with 6k - 1 <= √n
+fn last_k(n: u64) -> u64 {
+    (isqrt(n) + 1) / 6
+}
+
+// neither 6k - 1 nor 6k + 1 divides n for any k in [first, last]
+fn no_divisor(n: u64, first: u64, last: u64) -> bool {
+    (first..=last).all(|k| !n.is_multiple_of(6 * k - 1) && !n.is_multiple_of(6 * k + 1))
+}
+
+fn miller_rabin(n: u64) -> bool {
+    if n < 2 {
+        return false;
+    }
+    for p in SMALL_PRIMES {
+        if n == p {
+            return true;
+        }
+        if n.is_multiple_of(p) {
+            return false;
+        }
+    }
+    // no factor below 41 means anything smaller than 41^2 is prime
+    if n < 41 * 41 {
+        return true;
+    }
+
+    // write n - 1 = d * 2^s with d odd
+    let s = (n - 1).trailing_zeros();
+    let d = (n - 1) >> s;
+
+    WITNESSES.iter().all(|&w| {
+        let a = w % n;
+        // a witness divisible by n says nothing about n
+        a == 0 || strong_probable_prime(n, d, s, a)
+    })
+}
+
+// 2^64 <= n < WIDE_BOUND
+fn wide_miller_rabin(n: u128) -> bool {
+    if SMALL_PRIMES.iter().any(|&p| n.is_multiple_of(p as u128)) {
+        return false;
+    }
+
+    let s = (n - 1).trailing_zeros();
+    let d = (n - 1) >> s;
+    let mont = Montgomery::new(n);
+    let one = mont.one();
+    let minus_one = mont.sub(0, one);
+
+    WIDE_WITNESSES.iter().all(|&w| {
+        let mut x = mont.pow(mont.enter(w), d);
+        if x == one || x == minus_one {
+            return true;
+        }
+        for _ in 1..s {
+            x = mont.mul(x, x);
+            if x == minus_one {
+                return true;
+            }
+        }
+        false
+    })
+}
+
+// n passes the strong fermat test to base a
+fn strong_probable_prime(n: u64, d: u64, s: u32, a: u64) -> bool {
+    let mut x = pow_mod(a, d, n);
+    if x == 1 || x == n - 1 {
+        return true;
+    }
+    for _ in 1..s {
+        x = mul_mod(x, x, n);
+        if x == n - 1 {
+            return true;
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn miller_rabin_matches_trial_division() {
+        // exhaustive below 10^7
+        let mismatch = (0..10_000_000u64)
+            .into_par_iter()
+            .find_any(|&n| n.prime() != trial_division(n));
+        assert_eq!(None, mismatch);
+    }
+
+    #[test]
+    fn large_primes() {
+        let primes: [u64; 6] = [
+            4_294_967_291,
+            1_000_000_000_000_000_003,
+            2_305_843_009_213_693_951, // 2^61 - 1
+            9_223_372_036_854_775_783,
+            18_446_744_073_709_551_557, // largest u64 prime
+            18_446_744_069_414_584_321, // 2^64 - 2^32 + 1
+        ];
+        assert!(primes.iter().all(|&p| p.prime()));
+    }
+
+    #[test]
+    fn large_composites() {
+        let composites: [u64; 6] = [
+            3_215_031_751,             // strong pseudoprime to bases 2, 3, 5, 7
+            3_825_123_056_546_413_051, // strong pseudoprime to the first 9 prime bases
+            4_294_967_291 * 4_294_967_279,
+            1_000_000_007 * 998_244_353,
+            u64::MAX,
+            u64::MAX - 1,
+        ];
+        assert!(composites.iter().all(|&c| !c.prime()));
+    }
+
+    #[test]
+    fn narrow_and_wide_agree() {
+        for n in (0..100_000u32).chain(u32::MAX - 1000..=u32::MAX) {
+            assert_eq!((n as u64).prime(), n.prime(), "{n}");
+            assert_eq!((n as u64).prime(), (n as u128).prime(), "{n}");
+        }
+        for n in u64::MAX - 1000..=u64::MAX {
+            assert_eq!(n.prime(), (n as u128).prime(), "{n}");
+        }
+    }
+
+    #[test]
+    fn wide_primes() {
+        let primes: [u128; 6] = [
+            (1 << 64) + 13,
+            (1 << 89) - 1,
+            (1 << 107) - 1,
+            (1 << 127) - 1,
+            1_000_000_000_000_000_000_000_000_000_057, // 10^30 + 57
+            u128::MAX - 158,                           // largest u128 prime
+        ];
+        assert!(primes.iter().all(|&p| p.prime()));
+    }
+
+    #[test]
+    fn wide_composites() {
+        let composites: [u128; 8] = [
+            1 << 64,
+            (1 << 64) + 1,
+            u128::MAX,
+            ((1 << 61) - 1) * ((1 << 61) - 1),
+            18_446_744_073_709_551_557 * 18_446_744_073_709_551_533,
+            1_000_000_007 * 998_244_353 * 18_446_744_073_709_551_557,
+            // strong pseudoprime to the first 12 prime bases, 3.2 * 10^23
+            318_665_857_834_031_151_167_461,
+            // and to the first 13, the end of the proven range
+            WIDE_BOUND,
+        ];
+        assert!(composites.iter().all(|&c| !c.prime()));
+    }
+
+    #[test]
+    fn big_primes() {
+        let big = |s: &str| s.parse::<BigUint>().unwrap();
+        for n in [
+            0u128,
+            1,
+            2,
+            97,
+            561,
+            (1 << 127) - 1,
+            u128::MAX - 158,
+            u128::MAX,
+        ] {
+            assert_eq!(n.prime(), BigUint::from(n).prime(), "{n}");
+        }
+        // 2^521 - 1 and 2^607 - 1 are mersenne primes, 2^523 - 1 is not
+        let mersenne = |e| &(&BigUint::one() << e) - &BigUint::one();
+        assert!(mersenne(521).prime());
+        assert!(mersenne(607).prime());
+        assert!(!mersenne(523).prime());
+        // RSA-100
+        assert!(
+            !big("1522605027922533360535618378132637429718068114961380688657908494580122963258952897654000350692006139")
+                .prime()
+        );
+        assert!(big("37975227936943673922808872755445627854565536638199").prime());
+        assert!(big("40094690950920881030683735292761468389214899724061").prime());
+        // arnault's strong pseudoprime to every prime base below 307, p (313 (p - 1) + 1)
+        // (353 (p - 1) + 1) for a prime p of 131 digits
+        let arnault = big(concat!(
+            "28871482380507712126714295971303939919776094592797227009265160241974323037991527331163",
+            "28983144639225941977803110929349655578418949441740933805615113979999421542416933972905",
+            "42371100275104208013496673175515285922696291677532547504444585610194940420003990443211",
+            "67766199496295392504526987193290703735640322737012784538991261203092448414947289768854",
+            "06024976768122077071687938121709811322297802059565867"
+        ));
+        assert!(!(&arnault).prime());
+        assert!(!arnault.probable_prime());
+    }
+
+    #[test]
+    fn probable_primes_match_prime() {
+        for n in (0..200_000u64).chain(u64::MAX - 2000..=u64::MAX) {
+            assert_eq!(n.prime(), n.probable_prime(), "{n}");
+        }
+        for n in (1u128 << 64) - 1000..(1 << 64) + 1000 {
+            assert_eq!(n.prime(), n.probable_prime(), "{n}");
+            assert_eq!(n.prime(), BigUint::from(n).probable_prime(), "{n}");
+        } // miller-rabin below the bound, baillie-psw from it on
+        for n in WIDE_BOUND - 1000..WIDE_BOUND + 1000 {
+            assert_eq!(n.prime(), n.probable_prime(), "{n}");
+        }
+    }
+
+    #[test]
+    fn probable_prime_vectors() {
+        let big = |s: &str| s.parse::<BigUint>().unwrap();
+        // strong pseudoprimes to base 2, the first three small, the rest to many bases
+        let pseudoprimes: [u128; 7] = [
+            2047,
+            3277,
+            4033,
+            3_215_031_751,
+            3_825_123_056_546_413_051,
+            318_665_857_834_031_151_167_461,
+            3_317_044_064_679_887_385_961_981,
+        ];
+        // carmichael numbers, the last two chernick numbers (6k + 1)(12k + 1)(18k + 1)
+        let carmichael: [u128; 9] = [
+            561,
+            1105,
+            1729,
+            2465,
+            2821,
+            6601,
+            8911,
+            3_559_159_468_279_275_456_961,
+            3_561_073_604_231_442_858_289,
+        ];
+        for n in pseudoprimes.into_iter().chain(carmichael) {
+            assert!(!n.probable_prime(), "{n}");
+            assert!(!BigUint::from(n).probable_prime(), "{n}");
+        }
+
+        let mersenne = |e| &(&BigUint::one() << e) - &BigUint::one();
+        assert!(mersenne(521).probable_prime());
+        assert!(mersenne(1279).probable_prime());
+        assert!(!mersenne(1277).probable_prime());
+        assert!(((1u128 << 127) - 1).probable_prime());
+        assert!((u128::MAX - 158).probable_prime());
+        assert!(!u128::MAX.probable_prime());
+        // squares of primes, where no D has (D / n) = -1
+        assert!(!(65_537u128 * 65_537).probable_prime());
+        assert!(!((1u128 << 61) - 1).pow(2).probable_prime());
+        let p = big("37975227936943673922808872755445627854565536638199");
+        assert!((&p).probable_prime());
+        assert!(!(&p * &p).probable_prime());
+        assert!(
+            !(&p * &big("40094690950920881030683735292761468389214899724061")).probable_prime()
+        );
+    }
+
+    #[test]
+    fn small_trial_division() {
+        let primes: Vec<u64> = (0..100).filter(|&n| trial_division(n)).collect();
+        assert_eq!(
+            vec![
+                2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79,
+                83, 89, 97
+            ],
+            primes
+        );
+        // 6k ± 1 right at √n
+        assert!(!trial_division(25));
+        assert!(!trial_division(49));
+        assert!(!trial_division(1_000_003 * 1_000_003));
+    }
+
+    #[test]
+    fn parallel_trial_division() {
+        for n in [
+            (1 << 40) - 87, // prime just below 2^40
+            (1 << 40) + 15, // prime just above 2^40
+            1_048_573 * 1_048_583,
+            1_048_573 * 1_048_573,
+            1_000_003 * 1_000_033 * 5,
+            1 << 41,
+            3 << 40,
+            1_000_000_000_000_037,
+        ] {
+            assert_eq!(n.prime(), par_trial_division(n), "{n}");
+        }
+        assert!(par_trial_division(97));
+        assert!(!par_trial_division(91));
+    }
+}
@@ -0,0 +1,156 @@
+//! Arithmetic modulo `n` behind one trait, so the probable-prime test and the
+//! factoring stages are written once for [`Montgomery`] and [`BigMontgomery`].
+
+use crate::{
+    arith::gcd_u128,
+    bigint::BigUint,
+    montgomery::{BigMontgomery, Montgomery},
+};
+
+// arithmetic modulo n, on whatever representation the width uses
+pub(crate) trait Residues {
+    type T: Clone + PartialEq;
+
+    fn residue(&self, k: i64) -> Self::T;
+    fn add(&self, a: &Self::T, b: &Self::T) -> Self::T;
+    fn sub(&self, a: &Self::T, b: &Self::T) -> Self::T;
+    fn mul(&self, a: &Self::T, b: &Self::T) -> Self::T;
+    // a / 2 mod n for odd n
+    fn half(&self, a: &Self::T) -> Self::T;
+    // gcd(a, n) as a plain number if it is neither 1 nor n
+    fn divisor(&self, a: &Self::T) -> Option<Self::T>;
+    // n, and a divisor of n back in this width, for the methods that work on BigUint
+    fn big_modulus(&self) -> BigUint;
+    fn narrow(&self, d: BigUint) -> Self::T;
+
+    fn pow(&self, a: &Self::T, mut exp: u64) -> Self::T {
+        let mut result = self.residue(1);
+        let mut base = a.clone();
+        while exp > 0 {
+            if exp & 1 == 1 {
+                result = self.mul(&result, &base);
+            }
+            base = self.mul(&base, &base);
+            exp >>= 1;
+        }
+        result
+    }
+}
+
+impl Residues for Montgomery {
+    type T = u128;
+
+    fn residue(&self, k: i64) -> u128 {
+        let k_abs = self.enter(k.unsigned_abs() as u128);
+        if k < 0 { self.sub(0, k_abs) } else { k_abs }
+    }
+
+    fn add(&self, a: &u128, b: &u128) -> u128 {
+        Montgomery::add(self, *a, *b)
+    }
+
+    fn sub(&self, a: &u128, b: &u128) -> u128 {
+        Montgomery::sub(self, *a, *b)
+    }
+
+    fn mul(&self, a: &u128, b: &u128) -> u128 {
+        Montgomery::mul(self, *a, *b)
+    }
+
+    // halving commutes with the factor R of montgomery form
+    fn half(&self, a: &u128) -> u128 {
+        Montgomery::half(self, *a)
+    }
+
+    // a * R shares its factors with n exactly like a, R is a power of 2
+    fn divisor(&self, a: &u128) -> Option<u128> {
+        let g = gcd_u128(*a, self.modulus());
+        (g != 1 && g != self.modulus()).then_some(g)
+    }
+
+    fn big_modulus(&self) -> BigUint {
+        BigUint::from(self.modulus())
+    }
+
+    fn narrow(&self, d: BigUint) -> u128 {
+        d.to_u128().expect("a divisor of a u128 fits into u128")
+    }
+}
+
+impl Residues for BigMontgomery {
+    type T = BigUint;
+
+    fn residue(&self, k: i64) -> BigUint {
+        let k_abs = self.enter(&BigUint::from(k.unsigned_abs()));
+        if k < 0 {
+            self.sub(&BigUint::zero(), &k_abs)
+        } else {
+            k_abs
+        }
+    }
+
+    fn add(&self, a: &BigUint, b: &BigUint) -> BigUint {
+        BigMontgomery::add(self, a, b)
+    }
+
+    fn sub(&self, a: &BigUint, b: &BigUint) -> BigUint {
+        BigMontgomery::sub(self, a, b)
+    }
+
+    fn mul(&self, a: &BigUint, b: &BigUint) -> BigUint {
+        BigMontgomery::mul(self, a, b)
+    }
+
+    fn half(&self, a: &BigUint) -> BigUint {
+        BigMontgomery::half(self, a)
+    }
+
+    fn divisor(&self, a: &BigUint) -> Option<BigUint> {
+        let g = a.gcd(self.modulus());
+        (!g.is_one() && g != *self.modulus()).then_some(g)
+    }
+
+    fn big_modulus(&self) -> BigUint {
+        self.modulus().clone()
+    }
+
+    fn narrow(&self, d: BigUint) -> BigUint {
+        d
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn widths_agree() {
+        let n: u128 = 1_000_000_007 * 998_244_353;
+        let big = BigUint::from(n);
+        let mont = Montgomery::new(n);
+        let ring = BigMontgomery::new(&big);
+        // both sides are compared out of montgomery form, R differs between them
+        let narrow = |x: u128| BigUint::from(mont.leave(x));
+        let wide = |x: BigUint| ring.leave(&x);
+        for (a, b) in [(2, 3), (-1, -1), (n as i64 - 1, 7), (0, -5)] {
+            let (x, y) = (mont.residue(a), mont.residue(b));
+            let (u, v) = (ring.residue(a), ring.residue(b));
+            assert_eq!(narrow(x), wide(u.clone()));
+            assert_eq!(narrow(mont.mul(x, y)), wide(ring.mul(&u, &v)));
+            assert_eq!(narrow(mont.sub(x, y)), wide(ring.sub(&u, &v)));
+            assert_eq!(narrow(mont.half(x)), wide(ring.half(&u)));
+            assert_eq!(narrow(mont.pow(x, 1000)), wide(ring.pow(&u, 1000)));
+            assert_eq!(mont.pow(x, 1000), Residues::pow(&mont, &x, 1000));
+        }
+        assert_eq!(
+            Some(1_000_000_007),
+            mont.divisor(&mont.residue(1_000_000_007 * 5))
+        );
+        assert_eq!(None, mont.divisor(&mont.residue(0)));
+        assert_eq!(None, ring.divisor(&ring.residue(1)));
+        assert_eq!(
+            Some(BigUint::from(998_244_353u64)),
+            ring.divisor(&ring.residue(998_244_353 * 3))
+        );
+    }
+}
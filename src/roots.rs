@@ -0,0 +1,221 @@
+//! Exact integer roots.
+//!
+//! A float root is off by one or more above 2^53, these correct the float estimate
+//! with integer arithmetic, so `r` is exact for every `u64`, and [`wide_isqrt`] for
+//! every `u128`. [`exact_sqrt_big`] filters squares of a [`BigUint`] the same way
+//! before its Newton root.
+//!
+//! ```
+//! use prime_factorization::roots::{icbrt, iroot, isqrt, wide_isqrt};
+//!
+//! assert_eq!(4_294_967_291, isqrt(4_294_967_291 * 4_294_967_291 + 1));
+//! assert_eq!(u64::MAX, wide_isqrt(u128::MAX));
+//! assert_eq!(2_642_245, icbrt(u64::MAX));
+//! assert_eq!(65_535, iroot(u64::MAX, 4));
+//! ```
+
+use crate::bigint::BigUint;
+
+/// Largest `r` with `r * r <= n`.
+pub fn isqrt(n: u64) -> u64 {
+    iroot(n, 2)
+}
+
+/// Largest `r` with `r * r * r <= n`.
+pub fn icbrt(n: u64) -> u64 {
+    iroot(n, 3)
+}
+
+/// Largest `r` with `r^k <= n`.
+///
+/// # Panics
+///
+/// If `k` is 0.
+pub fn iroot(n: u64, k: u32) -> u64 {
+    assert!(k > 0, "the 0th root is undefined");
+    if k == 1 || n < 2 {
+        return n;
+    }
+    // 2^64 is the first power of 2 out of range, so r is 1 from here on
+    if k >= u64::BITS {
+        return 1;
+    }
+
+    let mut r = (n as f64).powf(1.0 / k as f64) as u64;
+    // the float estimate can be off in either direction
+    while r.checked_pow(k).is_none_or(|power| power > n) {
+        r -= 1;
+    }
+    while (r + 1).checked_pow(k).is_some_and(|power| power <= n) {
+        r += 1;
+    }
+    r
+}
+
+/// Largest `r` with `r * r <= n`, for `n` of up to 128 bits.
+pub fn wide_isqrt(n: u128) -> u64 {
+    // the float root is off by a little at most, and rounds up to 2^64 for u128::MAX
+    let mut r = ((n as f64).sqrt() as u128).min(u64::MAX as u128);
+    while r * r > n {
+        r -= 1;
+    }
+    while r < u64::MAX as u128 && (r + 1) * (r + 1) <= n {
+        r += 1;
+    }
+    r as u64
+}
+
+/// The square root of `n` if `n` is a perfect square, for [`BigUint`].
+pub fn exact_sqrt_big(n: &BigUint) -> Option<BigUint> {
+    if SQUARES_MOD_64 >> n.rem_u64(64) & 1 == 0 {
+        return None;
+    }
+    let r = n.isqrt();
+    (&r * &r == *n).then_some(r)
+}
+
+/// The square root of `n` if `n` is a perfect square.
+pub fn exact_sqrt(n: u64) -> Option<u64> {
+    // only 12 of the 64 residues modulo 64 are squares, most n end here
+    if SQUARES_MOD_64 >> (n % 64) & 1 == 0 {
+        return None;
+    }
+    // the float root is exact below 2^52 and off by at most 1 above
+    let mut r = (n as f64).sqrt() as u64;
+    if r.checked_mul(r).is_none_or(|square| square > n) {
+        r -= 1;
+    } else if (r + 1).checked_mul(r + 1).is_some_and(|square| square <= n) {
+        r += 1;
+    }
+    (r * r == n).then_some(r)
+}
+
+// bit i is set if i is a square modulo 64
+const SQUARES_MOD_64: u64 = {
+    let mut mask = 0u64;
+    let mut i = 0;
+    while i < 64 {
+        mask |= 1 << (i * i % 64);
+        i += 1;
+    }
+    mask
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // r is the k-th root of n iff r^k <= n < (r + 1)^k
+    fn is_root(n: u64, k: u32, r: u64) -> bool {
+        r.checked_pow(k).is_some_and(|power| power <= n)
+            && r.checked_add(1)
+                .and_then(|next| next.checked_pow(k))
+                .is_none_or(|power| power > n)
+    }
+
+    #[test]
+    fn small_roots() {
+        for n in 0..100_000 {
+            for k in 1..=6 {
+                assert!(is_root(n, k, iroot(n, k)), "{k}th root of {n}");
+            }
+        }
+        assert_eq!(0, isqrt(0));
+        assert_eq!(1, isqrt(3));
+        assert_eq!(2, isqrt(4));
+        assert_eq!(2, icbrt(26));
+        assert_eq!(3, icbrt(27));
+    }
+
+    #[test]
+    fn squares_of_large_primes() {
+        // the float estimate of √(p^2 - 1) rounds up to p
+        for p in [
+            4_294_967_291u64, // 2^32 - 5
+            4_294_967_279,
+            4_294_967_231,
+            3_037_000_493,
+            1_000_000_007,
+            94_906_249,
+            65_521,
+        ] {
+            let square = p * p;
+            for n in square - 1000..=square.saturating_add(1000) {
+                let r = isqrt(n);
+                assert!(is_root(n, 2, r), "√{n}");
+                assert_eq!(if n < square { p - 1 } else { p }, r);
+            }
+            assert_eq!(Some(p), exact_sqrt(square));
+            assert_eq!(None, exact_sqrt(square - 1));
+            assert_eq!(None, exact_sqrt(square + 1));
+        }
+    }
+
+    #[test]
+    fn cubes_of_large_primes() {
+        for p in [2_642_243u64, 2_097_143, 1_000_003, 65_537] {
+            let cube = p * p * p;
+            for n in cube - 1000..=cube + 1000 {
+                let r = icbrt(n);
+                assert!(is_root(n, 3, r), "∛{n}");
+                assert_eq!(if n < cube { p - 1 } else { p }, r);
+            }
+        }
+    }
+
+    #[test]
+    fn roots_near_u64_max() {
+        assert_eq!(u32::MAX as u64, isqrt(u64::MAX));
+        assert_eq!(u32::MAX as u64 - 1, isqrt((u32::MAX as u64).pow(2) - 1));
+        assert_eq!(2_642_245, icbrt(u64::MAX));
+        assert_eq!(65_535, iroot(u64::MAX, 4));
+        assert_eq!(1, iroot(u64::MAX, 64));
+        assert_eq!(2, iroot(u64::MAX, 63));
+        assert_eq!(1, iroot(u64::MAX, 100));
+        assert_eq!(0, iroot(0, 100));
+        for k in 1..=70 {
+            assert!(is_root(u64::MAX, k, iroot(u64::MAX, k)));
+            assert!(is_root(u64::MAX - 1, k, iroot(u64::MAX - 1, k)));
+        }
+    }
+
+    #[test]
+    fn wide_roots() {
+        for n in (0..100_000u64).chain(u64::MAX - 1000..=u64::MAX) {
+            assert_eq!(isqrt(n), wide_isqrt(n as u128), "{n}");
+        }
+        for p in [
+            4_294_967_291u128,
+            18_446_744_073_709_551_557,
+            u64::MAX as u128,
+        ] {
+            assert_eq!(p as u64, wide_isqrt(p * p), "{p}");
+            assert_eq!(p as u64 - 1, wide_isqrt(p * p - 1), "{p}");
+            assert_eq!(p as u64, wide_isqrt(p * p + 1), "{p}");
+        }
+        assert_eq!(u64::MAX, wide_isqrt(u128::MAX));
+    }
+
+    #[test]
+    fn big_squares() {
+        let big = |n: u128| BigUint::from(n);
+        for n in 0..10_000u128 {
+            assert_eq!(
+                exact_sqrt(n as u64).map(|r| big(r as u128)),
+                exact_sqrt_big(&big(n)),
+                "{n}"
+            );
+        }
+        let p = big(18_446_744_073_709_551_557);
+        let square = &(&p * &p) * &(&p * &p);
+        assert_eq!(Some(&p * &p), exact_sqrt_big(&square));
+        assert_eq!(None, exact_sqrt_big(&(&square + &big(1))));
+        assert_eq!(None, exact_sqrt_big(&(&square - &big(1))));
+    }
+
+    #[test]
+    #[should_panic]
+    fn zeroth_root() {
+        iroot(10, 0);
+    }
+}
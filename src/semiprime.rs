@@ -0,0 +1,548 @@
+//! Enumeration and counting of products of primes.
+
+use rayon::prelude::*;
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashSet},
+};
+
+/// All products `p * q` of two distinct primes as `(p * q, p, q)` with `p < q`.
+///
+/// This holds all O(π²) pairs at once, use [`semiprimes`], [`sorted_semiprimes`] or
+/// [`par_for_each_semiprime`] for large sets.
+pub fn factorize(primes: Vec<u64>) -> HashSet<(u128, u64, u64)> {
+    // only store (a * b, min(a, b), max(a, b)) to avoid redundant pairs like (6,2,3) and (6,3,2)
+    // this ensures each unique pair appears only once.
+    // the k = 2 case of almost_primes, without a factor vector per pair
+    primes
+        .par_iter()
+        .flat_map_iter(|&p| {
+            primes
+                .iter()
+                .filter(move |&&q| p < q)
+                .map(move |&q| (p as u128 * q as u128, p, q))
+        })
+        .collect()
+}
+
+/// Iterator returned by [`semiprimes`].
+pub struct Semiprimes<'a> {
+    primes: &'a [u64],
+    i: usize,
+    j: usize,
+}
+
+/// Lazily yields `(p * q, p, q)` for all pairs `p < q` of sorted, distinct primes,
+/// ordered by `p` and then `q`, without allocating.
+pub fn semiprimes(primes: &[u64]) -> Semiprimes<'_> {
+    Semiprimes { primes, i: 0, j: 1 }
+}
+
+impl Iterator for Semiprimes<'_> {
+    type Item = (u128, u64, u64);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.j >= self.primes.len() {
+            if self.i + 2 >= self.primes.len() {
+                return None;
+            }
+            self.i += 1;
+            self.j = self.i + 1;
+        }
+
+        let (p, q) = (self.primes[self.i], self.primes[self.j]);
+        self.j += 1;
+        Some((p as u128 * q as u128, p, q))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.primes.len();
+        // pairs left in the current row plus all pairs among the following primes
+        let remaining = len.saturating_sub(self.j)
+            + len.saturating_sub(self.i + 1) * len.saturating_sub(self.i + 2) / 2;
+        (remaining, Some(remaining))
+    }
+}
+
+impl ExactSizeIterator for Semiprimes<'_> {}
+
+/// Iterator returned by [`sorted_semiprimes`].
+pub struct SortedSemiprimes<'a> {
+    primes: &'a [u64],
+    heap: BinaryHeap<Reverse<(u128, usize, usize)>>,
+}
+
+/// Yields `(p * q, p, q)` for all pairs `p < q` of sorted, distinct primes in ascending
+/// order of `p * q`.
+///
+/// Every row `p * q_j, p * q_j+1, ...` is already sorted, so a heap merges the rows with one
+/// entry per prime instead of sorting all O(π²) products. Products are unique by unique
+/// factorization, so there are no ties to break.
+pub fn sorted_semiprimes(primes: &[u64]) -> SortedSemiprimes<'_> {
+    // only the first row is queued, row i joins once p_i * p_i+1 is reached
+    let mut heap = BinaryHeap::new();
+    if primes.len() > 1 {
+        heap.push(Reverse((primes[0] as u128 * primes[1] as u128, 0, 1)));
+    }
+    SortedSemiprimes { primes, heap }
+}
+
+impl Iterator for SortedSemiprimes<'_> {
+    type Item = (u128, u64, u64);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let Reverse((n, i, j)) = self.heap.pop()?;
+        let primes = self.primes;
+
+        if j + 1 < primes.len() {
+            let next = primes[i] as u128 * primes[j + 1] as u128;
+            self.heap.push(Reverse((next, i, j + 1)));
+        }
+        // the head of the next row is p_i+1 * p_i+2, which is larger than p_i * p_i+1
+        if j == i + 1 && j + 1 < primes.len() {
+            let head = primes[j] as u128 * primes[j + 1] as u128;
+            self.heap.push(Reverse((head, j, j + 1)));
+        }
+
+        Some((n, primes[i], primes[j]))
+    }
+}
+
+// products per batch of par_sorted_semiprimes, at least a few per prime so that
+// walking the rows does not outweigh the products
+const BATCH: usize = 1 << 20;
+
+/// Iterator returned by [`par_sorted_semiprimes`].
+pub struct ParSortedSemiprimes<'a> {
+    primes: &'a [u64],
+    // the next q of every row, rows before `first` are done and rows from `rows` on
+    // have not started
+    cursors: Vec<usize>,
+    first: usize,
+    rows: usize,
+    // the next batch covers the products in lo..lo + width
+    lo: u128,
+    width: u128,
+    target: usize,
+    left: u128,
+    batch: std::vec::IntoIter<(u128, u64, u64)>,
+}
+
+/// [`sorted_semiprimes`] in parallel, the same pairs in the same order.
+///
+/// The products are cut into consecutive windows of about a million. The pairs of a
+/// window are collected on all threads, one row `p * q_j, p * q_j+1, ...` per job,
+/// and sorted with `par_sort_unstable`, so memory stays bounded by the window. The
+/// width of the windows follows the density of the products.
+pub fn par_sorted_semiprimes(primes: &[u64]) -> ParSortedSemiprimes<'_> {
+    let lo = match primes {
+        [p, q, ..] => *p as u128 * *q as u128,
+        _ => 0,
+    };
+    ParSortedSemiprimes {
+        primes,
+        cursors: (1..primes.len()).collect(),
+        first: 0,
+        rows: 0,
+        lo,
+        width: lo.max(1),
+        target: BATCH.max(8 * primes.len()),
+        left: pair_count(primes),
+        batch: Vec::new().into_iter(),
+    }
+}
+
+impl ParSortedSemiprimes<'_> {
+    // the pairs with lo <= p * q < lo + width, sorted
+    fn next_batch(&mut self) -> Vec<(u128, u64, u64)> {
+        let primes = self.primes;
+        let hi = self.lo.saturating_add(self.width);
+        // rows start at p_i * p_i+1 in ascending order, and end at p_i * p_max likewise
+        while self.rows < self.cursors.len()
+            && primes[self.rows] as u128 * primes[self.rows + 1] as u128 <= hi
+        {
+            self.rows += 1;
+        }
+        let (first, rows) = (self.first, self.rows);
+
+        let mut batch: Vec<(u128, u64, u64)> = self.cursors[first..rows]
+            .par_iter_mut()
+            .enumerate()
+            .flat_map_iter(|(i, cursor)| {
+                let p = primes[first + i];
+                let start = *cursor;
+                while *cursor < primes.len() && (p as u128 * primes[*cursor] as u128) < hi {
+                    *cursor += 1;
+                }
+                primes[start..*cursor]
+                    .iter()
+                    .map(move |&q| (p as u128 * q as u128, p, q))
+            })
+            .collect();
+        // products are unique, no need to compare the factors
+        batch.par_sort_unstable_by_key(|&(n, _, _)| n);
+
+        while self.first < self.rows && self.cursors[self.first] == primes.len() {
+            self.first += 1;
+        }
+        // aim for batches of about the target size
+        if batch.len() < self.target / 2 {
+            self.width = self.width.saturating_mul(2);
+        } else if batch.len() > self.target * 2 {
+            self.width = (self.width / 2).max(1);
+        }
+        self.lo = hi;
+        batch
+    }
+}
+
+impl Iterator for ParSortedSemiprimes<'_> {
+    type Item = (u128, u64, u64);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(triple) = self.batch.next() {
+                return Some(triple);
+            }
+            if self.left == 0 {
+                return None;
+            }
+            let batch = self.next_batch();
+            self.left -= batch.len() as u128;
+            self.batch = batch.into_iter();
+        }
+    }
+}
+
+/// Parallel version of [`semiprimes`], each rayon job feeds its pairs into its own sink
+/// created by `init`, so nothing is shared and memory stays bounded by the sinks.
+///
+/// Returns the sinks for merging, the order of the pairs across sinks is unspecified.
+pub fn par_for_each_semiprime<S, I, F>(primes: &[u64], init: I, op: F) -> Vec<S>
+where
+    S: Send,
+    I: Fn() -> S + Sync + Send,
+    F: Fn(&mut S, (u128, u64, u64)) + Sync + Send,
+{
+    primes
+        .par_iter()
+        .enumerate()
+        .fold(init, |mut sink, (i, &p)| {
+            for &q in &primes[i + 1..] {
+                op(&mut sink, (p as u128 * q as u128, p, q));
+            }
+            sink
+        })
+        .collect()
+}
+
+/// Number of pairs `p < q`, that is C(π, 2), without enumerating them.
+pub fn pair_count(primes: &[u64]) -> u128 {
+    let n = primes.len() as u128;
+    n * n.saturating_sub(1) / 2
+}
+
+/// Number of pairs `p < q` of sorted, distinct primes with `p * q <= bound`.
+pub fn semiprime_count(primes: &[u64], bound: u128) -> u128 {
+    let mut count = 0;
+    // as p grows the largest admissible q shrinks, so one pointer walks down once
+    let mut hi = primes.len();
+    for (i, &p) in primes.iter().enumerate() {
+        while hi > i + 1 && p as u128 * primes[hi - 1] as u128 > bound {
+            hi -= 1;
+        }
+        if hi <= i + 1 {
+            break;
+        }
+        count += (hi - i - 1) as u128;
+    }
+    count
+}
+
+/// All products of `k` primes from the set as `(product, factors)` with nondecreasing factors.
+///
+/// `include_repeats` allows a prime to appear more than once, e.g. 12 = 2 * 2 * 3 for k = 3.
+/// Products that do not fit into `u128` are skipped.
+pub fn almost_primes(primes: &[u64], k: usize, include_repeats: bool) -> Vec<(u128, Vec<u64>)> {
+    let mut primes = primes.to_vec();
+    primes.par_sort_unstable();
+    primes.dedup();
+
+    if k == 0 {
+        return vec![(1, Vec::new())];
+    }
+
+    primes
+        .par_iter()
+        .enumerate()
+        .flat_map_iter(|(i, &p)| {
+            let mut products = Vec::new();
+            let mut factors = Vec::with_capacity(k);
+            factors.push(p);
+            extend(
+                &primes,
+                if include_repeats { i } else { i + 1 },
+                k - 1,
+                p as u128,
+                include_repeats,
+                &mut factors,
+                &mut products,
+            );
+            products
+        })
+        .collect()
+}
+
+// append the remaining k factors starting at primes[from]
+fn extend(
+    primes: &[u64],
+    from: usize,
+    k: usize,
+    product: u128,
+    include_repeats: bool,
+    factors: &mut Vec<u64>,
+    products: &mut Vec<(u128, Vec<u64>)>,
+) {
+    if k == 0 {
+        products.push((product, factors.clone()));
+        return;
+    }
+
+    for (i, &p) in primes.iter().enumerate().skip(from) {
+        // primes are sorted, every later prime overflows as well
+        let Some(next) = product.checked_mul(p as u128) else {
+            break;
+        };
+        factors.push(p);
+        let from = if include_repeats { i } else { i + 1 };
+        extend(
+            primes,
+            from,
+            k - 1,
+            next,
+            include_repeats,
+            factors,
+            products,
+        );
+        factors.pop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn streamed_semiprimes() {
+        let primes = crate::collect_primes(0, 2000);
+        let streamed: Vec<_> = semiprimes(&primes).collect();
+        let expected = factorize(primes.clone());
+
+        assert_eq!(expected.len(), streamed.len());
+        assert_eq!(expected, streamed.iter().copied().collect());
+        assert_eq!((6, 2, 3), streamed[0]);
+        assert!(
+            streamed
+                .windows(2)
+                .all(|w| (w[0].1, w[0].2) < (w[1].1, w[1].2))
+        );
+    }
+
+    #[test]
+    fn streamed_size_hint() {
+        let primes = [2, 3, 5, 7, 11];
+        let mut pairs = semiprimes(&primes);
+        for remaining in (0..=10).rev() {
+            assert_eq!(remaining, pairs.len());
+            pairs.next();
+        }
+        assert_eq!(None, pairs.next());
+        assert_eq!(0, semiprimes(&[]).len());
+        assert_eq!(None, semiprimes(&[2]).next());
+    }
+
+    #[test]
+    fn parallel_sinks() {
+        let primes = crate::collect_primes(0, 5000);
+        let sinks = par_for_each_semiprime(&primes, Vec::new, |sink, pair| sink.push(pair));
+        let mut pairs: Vec<_> = sinks.into_iter().flatten().collect();
+        pairs.sort_unstable_by_key(|&(_, p, q)| (p, q));
+
+        assert_eq!(semiprimes(&primes).collect::<Vec<_>>(), pairs);
+
+        let count: usize = par_for_each_semiprime(&primes, || 0, |count, _| *count += 1)
+            .into_iter()
+            .sum();
+        assert_eq!(primes.len() * (primes.len() - 1) / 2, count);
+    }
+
+    #[test]
+    fn sorted_by_product() {
+        let primes = crate::collect_primes(0, 3000);
+        let mut expected: Vec<_> = semiprimes(&primes).collect();
+        expected.sort_unstable();
+
+        assert_eq!(expected, sorted_semiprimes(&primes).collect::<Vec<_>>());
+        assert_eq!(
+            vec![
+                (6, 2, 3),
+                (10, 2, 5),
+                (14, 2, 7),
+                (15, 3, 5),
+                (21, 3, 7),
+                (35, 5, 7)
+            ],
+            sorted_semiprimes(&[2, 3, 5, 7]).collect::<Vec<_>>()
+        );
+        assert_eq!(None, sorted_semiprimes(&[2]).next());
+    }
+
+    #[test]
+    fn sorted_in_parallel() {
+        // 1_779 primes give 1_581_531 pairs, more than one batch
+        for primes in [
+            crate::collect_primes(0, 15_250),
+            crate::collect_primes(1_000_000, 1_010_000),
+            crate::collect_primes(u64::MAX - 1000, u64::MAX),
+            vec![2, 3, 5, 7],
+            vec![2, u64::MAX - 58],
+        ] {
+            assert!(
+                par_sorted_semiprimes(&primes).eq(sorted_semiprimes(&primes)),
+                "{} primes from {}",
+                primes.len(),
+                primes[0]
+            );
+        }
+        assert_eq!(None, par_sorted_semiprimes(&[2]).next());
+        assert_eq!(None, par_sorted_semiprimes(&[]).next());
+    }
+
+    #[test]
+    fn counted_pairs() {
+        let primes = crate::collect_primes(0, 3000);
+        assert_eq!(semiprimes(&primes).len() as u128, pair_count(&primes));
+        assert_eq!(0, pair_count(&[]));
+        assert_eq!(0, pair_count(&[2]));
+        assert_eq!(3, pair_count(&[2, 3, 5]));
+    }
+
+    #[test]
+    fn counted_semiprimes() {
+        // 34 semiprimes up to 100, minus the squares 4, 9, 25 and 49
+        assert_eq!(30, semiprime_count(&crate::collect_primes(0, 50), 100));
+
+        let primes = crate::collect_primes(0, 3000);
+        for bound in [0, 5, 6, 7, 1000, 12345, 3000 * 3000, u128::MAX] {
+            let expected = semiprimes(&primes).filter(|&(n, _, _)| n <= bound).count();
+            assert_eq!(expected as u128, semiprime_count(&primes, bound));
+        }
+    }
+
+    #[test]
+    fn counted_products_of_a_range() {
+        // products p * q <= end of distinct primes p < q from start..=end, by brute force
+        let brute_force = |start: u64, end: u64| {
+            (0..=end)
+                .filter(|&n| match crate::factor(n)[..] {
+                    [(p, 1), (_, 1)] => p >= start,
+                    _ => false,
+                })
+                .count() as u128
+        };
+        for (start, end) in [
+            (0, 100),
+            (2, 1000),
+            (3, 1000),
+            (10, 1000),
+            (50, 100),
+            (30, 5000),
+        ] {
+            let primes = crate::collect_primes(start, end);
+            assert_eq!(
+                brute_force(start, end),
+                semiprime_count(&primes, end as u128),
+                "{start}..={end}"
+            );
+        }
+        assert_eq!(0, semiprime_count(&crate::collect_primes(50, 100), 100));
+    }
+
+    #[test]
+    fn semiprimes_with_squares() {
+        let products: Vec<u128> = almost_primes(&[2, 3, 5], 2, true)
+            .into_iter()
+            .map(|(n, _)| n)
+            .collect();
+        assert_eq!(vec![4, 6, 10, 9, 15, 25], products);
+    }
+
+    #[test]
+    fn three_almost_primes() {
+        assert_eq!(
+            vec![
+                (30, vec![2, 3, 5]),
+                (42, vec![2, 3, 7]),
+                (70, vec![2, 5, 7]),
+                (105, vec![3, 5, 7])
+            ],
+            almost_primes(&[7, 5, 3, 2], 3, false)
+        );
+        assert_eq!(
+            vec![
+                (8, vec![2, 2, 2]),
+                (12, vec![2, 2, 3]),
+                (18, vec![2, 3, 3]),
+                (27, vec![3, 3, 3])
+            ],
+            almost_primes(&[2, 3], 3, true)
+        );
+    }
+
+    #[test]
+    fn almost_primes_degenerate() {
+        assert_eq!(vec![(1, vec![])], almost_primes(&[2, 3], 0, false));
+        assert_eq!(
+            vec![(2, vec![2]), (3, vec![3])],
+            almost_primes(&[3, 2, 3], 1, false)
+        );
+        assert!(almost_primes(&[2, 3], 3, false).is_empty());
+        assert!(almost_primes(&[], 2, true).is_empty());
+    }
+
+    #[test]
+    fn almost_primes_overflow() {
+        // (2^64 - 59)^3 does not fit into u128, 2 * 2 * (2^64 - 59) does
+        let big = u64::MAX - 58;
+        assert_eq!(
+            vec![(4 * big as u128, vec![2, 2, big])],
+            almost_primes(&[2, big], 3, true)
+                .into_iter()
+                .filter(|(_, factors)| factors.contains(&big))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn k2_without_repeats_is_distinct_pairs() {
+        let primes = crate::collect_primes(0, 1000);
+        let mut pairs = Vec::new();
+        for &p in &primes {
+            for &q in &primes {
+                if p < q {
+                    pairs.push((p as u128 * q as u128, vec![p, q]));
+                }
+            }
+        }
+        assert_eq!(pairs, almost_primes(&primes, 2, false));
+        // factorize has its own loop for this case, unsorted and repeated primes included
+        let mut shuffled = primes.clone();
+        shuffled.reverse();
+        shuffled.extend_from_slice(&primes[..10]);
+        let expected: HashSet<(u128, u64, u64)> = pairs
+            .into_iter()
+            .map(|(n, factors)| (n, factors[0], factors[1]))
+            .collect();
+        assert_eq!(expected, factorize(shuffled));
+    }
+}
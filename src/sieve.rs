@@ -0,0 +1,153 @@
+//! Sieving primes in a range.
+
+use crate::roots::isqrt;
+use rayon::prelude::*;
+
+// numbers per segment, small enough for the segment to stay in L2 cache
+const SEGMENT_LEN: u64 = 1 << 18;
+
+/// All primes in `[start, end]` by a segmented sieve of Eratosthenes.
+///
+/// Segments are sieved in parallel, memory stays at one segment per thread plus the
+/// primes up to √end.
+pub fn segmented_sieve(start: u64, end: u64) -> Vec<u64> {
+    if start > end {
+        return Vec::new();
+    }
+
+    let base = base_primes(isqrt(end) as u32);
+    let segments = (end - start) / SEGMENT_LEN + 1;
+
+    (0..segments)
+        .into_par_iter()
+        .flat_map_iter(|i| {
+            let lo = start + i * SEGMENT_LEN;
+            let hi = lo.saturating_add(SEGMENT_LEN - 1).min(end);
+            sieve_segment(lo, hi, &base)
+        })
+        .collect()
+}
+
+// all primes <= limit, used to cross off composites in the segments
+fn base_primes(limit: u32) -> Vec<u32> {
+    if (limit as u64) < SEGMENT_LEN {
+        return simple_sieve(limit);
+    }
+
+    // the base primes themselves are sieved segment by segment,
+    // so sieving up to 2^64 never needs a 2^32 sized array
+    let small = simple_sieve(isqrt(limit as u64) as u32);
+    let segments = limit as u64 / SEGMENT_LEN + 1;
+
+    (0..segments)
+        .into_par_iter()
+        .flat_map_iter(|i| {
+            let lo = i * SEGMENT_LEN;
+            let hi = (lo + SEGMENT_LEN - 1).min(limit as u64);
+            sieve_segment(lo, hi, &small).into_iter().map(|p| p as u32)
+        })
+        .collect()
+}
+
+fn simple_sieve(limit: u32) -> Vec<u32> {
+    let limit = limit as usize;
+    let mut composite = vec![false; limit + 1];
+    let mut primes = Vec::new();
+
+    for n in 2..=limit {
+        if composite[n] {
+            continue;
+        }
+        primes.push(n as u32);
+        for m in (n * n..=limit).step_by(n) {
+            composite[m] = true;
+        }
+    }
+
+    primes
+}
+
+// sieve [lo, hi] with the given base primes, which must cover √hi
+fn sieve_segment(lo: u64, hi: u64, base: &[u32]) -> Vec<u64> {
+    let len = (hi - lo + 1) as usize;
+    let mut composite = vec![false; len];
+
+    // 0 and 1 are never prime
+    for n in lo..=hi.min(1) {
+        composite[(n - lo) as usize] = true;
+    }
+
+    for &p in base {
+        let p = p as u64;
+        let square = p * p;
+        if square > hi {
+            break;
+        }
+
+        // first multiple of p inside the segment, skipping p itself
+        let first = match lo.div_ceil(p).checked_mul(p) {
+            Some(multiple) => multiple.max(square),
+            None => continue,
+        };
+        if first > hi {
+            continue;
+        }
+
+        for i in ((first - lo) as usize..len).step_by(p as usize) {
+            composite[i] = true;
+        }
+    }
+
+    composite
+        .iter()
+        .enumerate()
+        .filter(|&(_, &c)| !c)
+        .map(|(i, _)| lo + i as u64)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the primes of the range by trial division, independent of miller-rabin
+    fn trial_division(start: u64, end: u64) -> Vec<u64> {
+        (start..=end)
+            .filter(|&n| crate::prime::trial_division(n))
+            .collect()
+    }
+
+    #[test]
+    fn sieve_matches_trial_division() {
+        for (start, end) in [
+            (0, 0),
+            (0, 1),
+            (0, 2),
+            (2, 2),
+            (0, 1000),
+            (997, 1009),
+            (14, 16),
+        ] {
+            assert_eq!(trial_division(start, end), segmented_sieve(start, end));
+        }
+    }
+
+    #[test]
+    fn sieve_across_segments() {
+        let start = SEGMENT_LEN - 1000;
+        let end = 3 * SEGMENT_LEN + 1000;
+        assert_eq!(trial_division(start, end), segmented_sieve(start, end));
+    }
+
+    #[test]
+    fn sieve_large_range() {
+        let start = 1_000_000_000_000;
+        let end = start + 10_000;
+        assert_eq!(trial_division(start, end), segmented_sieve(start, end));
+    }
+
+    #[test]
+    fn sieve_reversed_range() {
+        assert!(segmented_sieve(10, 1).is_empty());
+    }
+}
@@ -0,0 +1,979 @@
+//! The self-initializing quadratic sieve, for composites without small prime factors.
+//!
+//! Every `x` where `(Ax + B)^2 - kn = A Q(x)` has only primes of the factor base is a
+//! relation `(Ax + B)^2 ≡ A Q(x) (mod n)`. Once there are more relations than primes,
+//! Gaussian elimination over GF(2) picks subsets whose products are squares on both
+//! sides, and each of them splits `n` with probability 1/2. Structured elimination
+//! shrinks the sparse matrix to under a third before the dense elimination, which
+//! takes a few seconds for the 24000 primes of 90 digits.
+//!
+//! `A` is a product of `s` primes of the factor base, which gives `2^(s - 1)` values of
+//! `B` whose roots modulo every prime follow from the last by one addition. Relations
+//! with one prime above the factor base are kept until a second one shares it.
+//!
+//! ```
+//! use prime_factorization::{bigint::BigUint, siqs::siqs};
+//!
+//! // two 15 digit primes
+//! let (p, q) = (100_000_000_000_031u128, 300_000_000_000_089u128);
+//! let d = siqs(&BigUint::from(p * q)).unwrap();
+//! assert!(d == BigUint::from(p) || d == BigUint::from(q));
+//! ```
+
+use crate::{
+    ProbablePrime,
+    arith::{mul_mod, pow_mod},
+    bigint::BigUint,
+    collect_primes,
+};
+use rayon::prelude::*;
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, HashSet, hash_map::Entry},
+};
+
+// (digits of n, size of the factor base, half width M of the sieve interval)
+const PARAMETERS: [(u32, usize, u32); 8] = [
+    (20, 100, 8_192),
+    (30, 200, 16_384),
+    (40, 500, 16_384),
+    (50, 1_200, 32_768),
+    (60, 3_500, 32_768),
+    (70, 6_000, 65_536),
+    (80, 12_000, 65_536),
+    (90, 24_000, 131_072),
+];
+
+// odd squarefree multipliers k, the one with the most small primes dividing values of
+// Q(x) wins
+const MULTIPLIERS: [u64; 24] = [
+    1, 3, 5, 7, 11, 13, 15, 17, 19, 21, 23, 29, 31, 33, 35, 37, 39, 41, 43, 47, 51, 53, 55, 57,
+];
+
+// primes below this are trial divided but not sieved, they cost the most and add little
+const UNSIEVED: u32 = 16;
+
+// bits that typical values of Q(x) are below the largest ones
+const SMALLER_VALUES: f64 = 5.0;
+
+// partial relations keep a cofactor up to this times the largest prime of the factor base
+const LARGE_PRIME_FACTOR: u64 = 64;
+
+// relations collected beyond the number of columns, and added per retry
+const EXTRA_RELATIONS: usize = 32;
+
+// rounds of linear algebra before giving up, n is a prime power if all of them fail
+const ROUNDS: usize = 4;
+
+// structured elimination merges the rows of columns with up to this many rows, through
+// a shortest row of up to this many columns, and keeps this many rows beyond the columns
+const MERGE_WEIGHT: usize = 32;
+const MAX_MERGED_LENGTH: usize = 128;
+const SURPLUS_ROWS: usize = 64;
+
+// columns per block of the dense elimination, a table of 2^BLOCK rows each
+const BLOCK: usize = 8;
+
+// the largest prime of A, smaller primes give more values of B per A
+const MAX_A_PRIME: u32 = 4_096;
+
+/// A proper divisor of `n`, found by the self-initializing quadratic sieve.
+///
+/// Meant for composites of 30 to 90 digits whose prime factors are all too large for
+/// ECM, the time only depends on the size of `n`. `None` if `n` is prime or the power
+/// of a prime other than a square, no congruence of squares splits those.
+pub fn siqs(n: &BigUint) -> Option<BigUint> {
+    if n.bits() <= 1 || n.probable_prime() {
+        return None;
+    }
+    let root = n.isqrt();
+    if &root * &root == *n {
+        return Some(root);
+    }
+    match Siqs::new(n) {
+        Ok(siqs) => siqs.run(),
+        // n itself is a small prime
+        Err(d) => (d != *n).then_some(d),
+    }
+}
+
+// a prime of the factor base with sqrt(kn) mod p and round(log2 p)
+struct Base {
+    p: u32,
+    root: u32,
+    log: u8,
+}
+
+// the values of B for one A, B_j^2 = kn mod q_j and B_j = 0 mod the other primes of A
+struct Family {
+    a: BigUint,
+    primes: Vec<usize>,
+    parts: Vec<BigUint>,
+}
+
+// (A x + B) mod n with the columns of A Q(x): 0 for -1, i + 1 for the prime i of the
+// factor base, and the product of the primes outside the base whose square it also has
+#[derive(Clone)]
+struct Relation {
+    u: BigUint,
+    columns: Vec<u32>,
+    large: BigUint,
+}
+
+struct Siqs<'a> {
+    n: &'a BigUint,
+    kn: BigUint,
+    k: u64,
+    base: Vec<Base>,
+    half_width: u32,
+    threshold: u8,
+    large_bound: u64,
+    // the primes of A: their count and the indices of the base they come from
+    a_primes: usize,
+    a_window: Vec<usize>,
+    a_bits: f64,
+}
+
+impl<'a> Siqs<'a> {
+    // the factor base and sieve parameters for n, Err with a factor of n found on the way
+    fn new(n: &'a BigUint) -> Result<Self, BigUint> {
+        let digits = n.to_string().len() as u32;
+        let (_, size, half_width) = PARAMETERS
+            .into_iter()
+            .find(|&(max, _, _)| digits <= max)
+            .unwrap_or(PARAMETERS[PARAMETERS.len() - 1]);
+
+        let k = multiplier(n);
+        let kn = n * &BigUint::from(k);
+        let mut base = vec![Base {
+            p: 2,
+            root: 1,
+            log: 1,
+        }];
+        // about every second prime is in the base, double the range until it is full
+        let mut end = 16 * size as u64;
+        let mut start = 3;
+        while base.len() < size {
+            for p in collect_primes(start, end) {
+                if n.rem_u64(p) == 0 {
+                    return Err(BigUint::from(p));
+                }
+                let r = kn.rem_u64(p);
+                if (r == 0 || pow_mod(r, (p - 1) / 2, p) == 1) && base.len() < size {
+                    base.push(Base {
+                        p: p as u32,
+                        root: sqrt_mod(r, p) as u32,
+                        log: (p as f64).log2().round() as u8,
+                    });
+                }
+            }
+            (start, end) = (end + 1, 2 * end);
+        }
+
+        // the largest values of Q(x) are about M sqrt(kn / 2), a relation has to cover
+        // all of it but the unsieved primes and a large prime, and most values are
+        // smaller than the largest
+        let largest = base[base.len() - 1].p as u64;
+        let large_bound = LARGE_PRIME_FACTOR * largest;
+        let bits = kn.bits() as f64 / 2.0 + (half_width as f64).log2() - 0.5;
+        let slack = (large_bound as f64).log2() + (UNSIEVED as f64).log2() + SMALLER_VALUES;
+        let threshold = (bits - slack).max(1.0) as u8;
+
+        // A close to sqrt(2 kn) / M keeps Q(x) smallest over the interval
+        let a_bits = (kn.bits() as f64 + 1.0) / 2.0 - (half_width as f64).log2();
+        let a_max = base[base.len() / 2].p.min(MAX_A_PRIME);
+        let a_primes = (a_bits / (a_max as f64).log2()).ceil().max(1.0) as usize;
+        let ideal = a_bits / a_primes as f64;
+        let center = base.partition_point(|b| (b.p as f64).log2() < ideal);
+        let width = (base.len() / 8).max(10);
+        let a_window = (center.saturating_sub(width)..(center + width).min(base.len()))
+            .filter(|&i| base[i].p >= UNSIEVED && !k.is_multiple_of(base[i].p as u64))
+            .collect();
+
+        Ok(Siqs {
+            n,
+            kn,
+            k,
+            base,
+            half_width,
+            threshold,
+            large_bound,
+            a_primes,
+            a_window,
+            a_bits,
+        })
+    }
+
+    fn run(&self) -> Option<BigUint> {
+        let columns = self.base.len() + 1;
+        let mut needed = columns + EXTRA_RELATIONS;
+        let mut full = Vec::new();
+        let mut partial: HashMap<u64, Relation> = HashMap::new();
+        let mut rng = XorShift(0x2545_f491_4f6c_dd1d);
+        let mut used = HashSet::new();
+
+        for _ in 0..ROUNDS {
+            while full.len() < needed {
+                // one A per thread, each sieves all of its B
+                let families: Vec<Family> = (0..rayon::current_num_threads())
+                    .filter_map(|_| self.family(&mut rng, &mut used))
+                    .collect();
+                if families.is_empty() {
+                    return None;
+                }
+                let found: Vec<(Relation, u64)> = families
+                    .par_iter()
+                    .flat_map_iter(|family| self.relations(family))
+                    .collect();
+
+                for (relation, cofactor) in found {
+                    if cofactor == 1 {
+                        full.push(relation);
+                        continue;
+                    }
+                    match partial.entry(cofactor) {
+                        Entry::Occupied(other) => {
+                            full.push(self.combine(other.get(), &relation, cofactor))
+                        }
+                        Entry::Vacant(entry) => {
+                            entry.insert(relation);
+                        }
+                    }
+                }
+            }
+
+            let rows: Vec<&[u32]> = full.iter().map(|r: &Relation| &r.columns[..]).collect();
+            for subset in dependencies(&rows, columns) {
+                if let Some(d) = self.square_root(&full, &subset) {
+                    return Some(d);
+                }
+            }
+            needed += EXTRA_RELATIONS;
+        }
+        None
+    }
+
+    // a random A of primes from the window that was not used before, and its B_j
+    fn family(&self, rng: &mut XorShift, used: &mut HashSet<Vec<usize>>) -> Option<Family> {
+        let window = &self.a_window;
+        if window.len() < self.a_primes {
+            return None;
+        }
+        for _ in 0..100 {
+            let mut primes = Vec::with_capacity(self.a_primes);
+            while primes.len() < self.a_primes - 1 {
+                let i = window[rng.below(window.len())];
+                if !primes.contains(&i) {
+                    primes.push(i);
+                }
+            }
+            // the last prime brings A closest to its ideal size
+            let bits: f64 = primes.iter().map(|&i| (self.base[i].p as f64).log2()).sum();
+            let rest = self.a_bits - bits;
+            let &last = window
+                .iter()
+                .filter(|i| !primes.contains(i))
+                .min_by(|&&i, &&j| {
+                    let (di, dj) = (
+                        ((self.base[i].p as f64).log2() - rest).abs(),
+                        ((self.base[j].p as f64).log2() - rest).abs(),
+                    );
+                    di.total_cmp(&dj)
+                })?;
+            primes.push(last);
+            primes.sort_unstable();
+            if !used.insert(primes.clone()) {
+                continue;
+            }
+
+            let a = primes.iter().fold(BigUint::one(), |a, &i| {
+                &a * &BigUint::from(self.base[i].p as u64)
+            });
+            let parts = primes
+                .iter()
+                .map(|&i| {
+                    let Base { p, root, .. } = self.base[i];
+                    let (p, root) = (p as u64, root as u64);
+                    let others = a.div_rem_u64(p).0;
+                    let inverse = pow_mod(others.rem_u64(p), p - 2, p);
+                    let mut gamma = mul_mod(root, inverse, p);
+                    if gamma > p / 2 {
+                        gamma = p - gamma;
+                    }
+                    &others * &BigUint::from(gamma)
+                })
+                .collect();
+            return Some(Family { a, primes, parts });
+        }
+        None
+    }
+
+    // sieves every B of the family, as relations with their cofactor, 1 for full ones
+    fn relations(&self, family: &Family) -> Vec<(Relation, u64)> {
+        let base = &self.base;
+        let m = self.half_width as u64;
+        let s = family.parts.len();
+
+        // roots of Q(x) shifted by M, x = (±sqrt(kn) - B) / A mod p, and their change
+        // when B_j flips its sign: 2 B_j / A mod p
+        let mut sieved = vec![false; base.len()];
+        let mut roots = vec![(0u32, 0u32); base.len()];
+        let mut deltas = vec![vec![0u32; base.len()]; s];
+        for (i, b) in base.iter().enumerate() {
+            let p = b.p as u64;
+            if !self.sieved(family, i) {
+                continue;
+            }
+            sieved[i] = true;
+            let inverse = pow_mod(family.a.rem_u64(p), p - 2, p);
+            let mut sum = 0;
+            for (j, part) in family.parts.iter().enumerate() {
+                let part = part.rem_u64(p);
+                sum = (sum + part) % p;
+                deltas[j][i] = mul_mod(2 * part, inverse, p) as u32;
+            }
+            let root = b.root as u64;
+            let shift = |r: u64| ((mul_mod(r, inverse, p) + m) % p) as u32;
+            roots[i] = (shift((root + p - sum) % p), shift((2 * p - root - sum) % p));
+        }
+
+        let mut found = Vec::new();
+        let mut sieve = vec![0u8; 2 * m as usize];
+        let mut negative = vec![false; s];
+        for index in 0..1usize << (s - 1) {
+            if index > 0 {
+                // gray code, one B_j flips from B to the next
+                let j = index.trailing_zeros() as usize;
+                for (i, b) in base.iter().enumerate() {
+                    if sieved[i] {
+                        let (p, d) = (b.p, deltas[j][i]);
+                        // B - 2 B_j moves the roots up by delta, B + 2 B_j down
+                        let d = if negative[j] { p - d } else { d };
+                        let (r1, r2) = roots[i];
+                        roots[i] = ((r1 + d) % p, (r2 + d) % p);
+                    }
+                }
+                negative[j] = !negative[j];
+            }
+
+            sieve.fill(0);
+            for (i, b) in base.iter().enumerate() {
+                if !sieved[i] {
+                    continue;
+                }
+                let p = b.p as usize;
+                let (r1, r2) = roots[i];
+                for start in [r1 as usize, r2 as usize] {
+                    for cell in sieve.iter_mut().skip(start).step_by(p) {
+                        *cell += b.log;
+                    }
+                }
+            }
+
+            // B as the difference of its positive and negative parts
+            let (mut plus, mut minus) = (BigUint::zero(), BigUint::zero());
+            for (part, &negative) in family.parts.iter().zip(&negative) {
+                if negative {
+                    minus = &minus + part;
+                } else {
+                    plus = &plus + part;
+                }
+            }
+            for (i, &value) in sieve.iter().enumerate() {
+                if value >= self.threshold
+                    && let Some(relation) = self.trial_divide(family, &plus, &minus, &roots, i)
+                {
+                    found.push(relation);
+                }
+            }
+        }
+        found
+    }
+
+    // whether the prime i of the base has two roots to sieve with, those of k and A have
+    // one, the small ones are left out
+    fn sieved(&self, family: &Family, i: usize) -> bool {
+        let p = self.base[i].p;
+        p >= UNSIEVED && !self.k.is_multiple_of(p as u64) && !family.primes.contains(&i)
+    }
+
+    // Q(x) = ((A x + B)^2 - kn) / A for x = i - M over the factor base, None unless the
+    // cofactor is 1 or a large prime
+    fn trial_divide(
+        &self,
+        family: &Family,
+        plus: &BigUint,
+        minus: &BigUint,
+        roots: &[(u32, u32)],
+        i: usize,
+    ) -> Option<(Relation, u64)> {
+        let m = self.half_width as usize;
+        let ax = &family.a * &BigUint::from(i.abs_diff(m) as u64);
+        let (high, low) = if i >= m {
+            (plus + &ax, minus.clone())
+        } else {
+            (plus.clone(), minus + &ax)
+        };
+        let u = high.abs_diff(&low);
+        let square = &u * &u;
+
+        let mut columns: Vec<u32> = family.primes.iter().map(|&j| j as u32 + 1).collect();
+        if square < self.kn {
+            columns.push(0);
+        }
+        let mut q = &square.abs_diff(&self.kn) / &family.a;
+        for (j, b) in self.base.iter().enumerate() {
+            let p = b.p as u64;
+            let (r1, r2) = roots[j];
+            // the roots only hold for sieved primes, the others are checked directly
+            let hit = if !self.sieved(family, j) {
+                q.rem_u64(p) == 0
+            } else {
+                let r = (i % b.p as usize) as u32;
+                r == r1 || r == r2
+            };
+            if !hit {
+                continue;
+            }
+            loop {
+                let (quotient, rem) = q.div_rem_u64(p);
+                if rem != 0 {
+                    break;
+                }
+                q = quotient;
+                columns.push(j as u32 + 1);
+            }
+        }
+
+        let cofactor = q.to_u64().filter(|&c| c < self.large_bound)?;
+        let relation = Relation {
+            u: &u % self.n,
+            columns,
+            large: BigUint::one(),
+        };
+        Some((relation, cofactor))
+    }
+
+    // two partial relations with the same large prime make a full one
+    fn combine(&self, a: &Relation, b: &Relation, large: u64) -> Relation {
+        Relation {
+            u: &(&a.u * &b.u) % self.n,
+            columns: [&a.columns[..], &b.columns[..]].concat(),
+            large: BigUint::from(large),
+        }
+    }
+
+    // x^2 = y^2 mod n from the relations of a dependency, a factor unless x = ±y
+    fn square_root(&self, relations: &[Relation], subset: &[usize]) -> Option<BigUint> {
+        let n = self.n;
+        let mut x = BigUint::one();
+        let mut y = BigUint::one();
+        let mut counts = vec![0u32; self.base.len() + 1];
+        for &r in subset {
+            let relation = &relations[r];
+            x = &(&x * &relation.u) % n;
+            y = &(&y * &relation.large) % n;
+            for &c in &relation.columns {
+                counts[c as usize] += 1;
+            }
+        }
+        for (b, &count) in self.base.iter().zip(&counts[1..]) {
+            if count > 0 {
+                let power = BigUint::from(b.p as u64).pow_mod(&BigUint::from(count as u64 / 2), n);
+                y = &(&y * &power) % n;
+            }
+        }
+        let d = x.abs_diff(&y).gcd(n);
+        (!d.is_one() && d != *n).then_some(d)
+    }
+}
+
+// knuth-schroeppel: the expected contribution of the small primes to log Q(x), less
+// the growth of Q(x) by sqrt(k)
+fn multiplier(n: &BigUint) -> u64 {
+    let primes = collect_primes(3, 1_000);
+    let score = |k: u64| {
+        let kn = |m: u64| mul_mod(n.rem_u64(m), k % m, m);
+        let ln2 = 2f64.ln();
+        let mut score = -0.5 * (k as f64).ln()
+            + match kn(8) {
+                1 => 2.0 * ln2,
+                5 => ln2,
+                _ => 0.5 * ln2,
+            };
+        for &p in &primes {
+            let ln = (p as f64).ln();
+            if k.is_multiple_of(p) {
+                score += ln / p as f64;
+            } else if pow_mod(kn(p), (p - 1) / 2, p) == 1 {
+                score += 2.0 * ln / (p - 1) as f64;
+            }
+        }
+        score
+    };
+    MULTIPLIERS
+        .into_iter()
+        .max_by(|&a, &b| score(a).total_cmp(&score(b)))
+        .expect("there are multipliers")
+}
+
+// a square root of a modulo the odd prime p by tonelli-shanks, a has to be a square
+fn sqrt_mod(a: u64, p: u64) -> u64 {
+    if a == 0 || p == 2 {
+        return a % p;
+    }
+    if p % 4 == 3 {
+        return pow_mod(a, (p + 1) / 4, p);
+    }
+    let s = (p - 1).trailing_zeros();
+    let q = (p - 1) >> s;
+    let mut z = 2;
+    while pow_mod(z, (p - 1) / 2, p) != p - 1 {
+        z += 1;
+    }
+
+    let (mut m, mut c) = (s, pow_mod(z, q, p));
+    let (mut t, mut r) = (pow_mod(a, q, p), pow_mod(a, q.div_ceil(2), p));
+    while t != 1 {
+        // the least i with t^(2^i) = 1
+        let mut i = 0;
+        let mut t2 = t;
+        while t2 != 1 {
+            t2 = mul_mod(t2, t2, p);
+            i += 1;
+        }
+        let b = pow_mod(c, 1 << (m - i - 1), p);
+        m = i;
+        c = mul_mod(b, b, p);
+        t = mul_mod(t, c, p);
+        r = mul_mod(r, b, p);
+    }
+    r
+}
+
+// subsets of the rows in which every column appears an even number of times
+//
+// structured gaussian elimination first shrinks the sparse matrix: it drops rows with
+// the only entry of a column, merges the rows of the light columns into one fewer row
+// without the column, and drops the heaviest rows beyond a small surplus. dense
+// elimination only runs on what is left, a fraction of the factor base
+fn dependencies(rows: &[&[u32]], columns: usize) -> Vec<Vec<usize>> {
+    // the columns with an odd count in each row, and the single row it stands for
+    let sparse: Vec<Option<Sparse>> = rows
+        .iter()
+        .enumerate()
+        .map(|(r, row)| {
+            let mut row = row.to_vec();
+            row.sort_unstable();
+            let mut odd: Vec<u32> = Vec::new();
+            for c in row {
+                if odd.last() == Some(&c) {
+                    odd.pop();
+                } else {
+                    odd.push(c);
+                }
+            }
+            Some(Sparse {
+                columns: odd,
+                rows: vec![r as u32],
+            })
+        })
+        .collect();
+    let reduced = reduce(sparse, columns);
+
+    // every dependency of the reduced rows stands for the sum of their original rows
+    dense_dependencies(&reduced, columns)
+        .into_iter()
+        .map(|subset| {
+            let mut original = Vec::new();
+            for k in subset {
+                original = symmetric_difference(&original, &reduced[k].rows);
+            }
+            original.into_iter().map(|r| r as usize).collect()
+        })
+        .filter(|subset: &Vec<usize>| !subset.is_empty())
+        .collect()
+}
+
+// a row of the sparse matrix, both lists sorted: the columns with an odd count and the
+// original rows it is the sum of
+struct Sparse {
+    columns: Vec<u32>,
+    rows: Vec<u32>,
+}
+
+// the elements in exactly one of two sorted lists, the sum over GF(2)
+fn symmetric_difference(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut sum = Vec::with_capacity(a.len() + b.len());
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        match a[i].cmp(&b[j]) {
+            std::cmp::Ordering::Less => {
+                sum.push(a[i]);
+                i += 1;
+            }
+            std::cmp::Ordering::Greater => {
+                sum.push(b[j]);
+                j += 1;
+            }
+            std::cmp::Ordering::Equal => {
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    sum.extend_from_slice(&a[i..]);
+    sum.extend_from_slice(&b[j..]);
+    sum
+}
+
+// the structured elimination, lightest columns first until no step applies
+fn reduce(mut sparse: Vec<Option<Sparse>>, columns: usize) -> Vec<Sparse> {
+    let mut reduction = Reduction {
+        weight: vec![0; columns],
+        rows_of: vec![Vec::new(); columns],
+        light: BinaryHeap::new(),
+    };
+    for (r, row) in sparse.iter().enumerate() {
+        for &c in row.iter().flat_map(|row| &row.columns) {
+            reduction.weight[c as usize] += 1;
+            reduction.rows_of[c as usize].push(r as u32);
+        }
+    }
+    for c in 0..columns {
+        reduction.queue(c);
+    }
+
+    loop {
+        while let Some(Reverse((w, c))) = reduction.light.pop() {
+            if w != reduction.weight[c] {
+                continue;
+            }
+            let rows = reduction.rows(&sparse, c);
+            if let [r] = rows[..] {
+                // the only entry of a column, the row is in no dependency
+                reduction.drop_row(&mut sparse, r);
+                continue;
+            }
+            // the shortest row is added to the others and dropped, which leaves w - 1
+            // rows without the column. merging a long row would fill the matrix instead
+            let length = |r: u32| {
+                sparse[r as usize]
+                    .as_ref()
+                    .map_or(0, |row| row.columns.len())
+            };
+            let Some(pivot) = rows.iter().copied().min_by_key(|&r| length(r)) else {
+                continue;
+            };
+            if length(pivot) > MAX_MERGED_LENGTH {
+                continue;
+            }
+            let pivot_row = reduction.drop_row(&mut sparse, pivot);
+            for &r in rows.iter().filter(|&&r| r != pivot) {
+                let row = sparse[r as usize]
+                    .as_mut()
+                    .expect("rows of a column are live");
+                for &c in &pivot_row.columns {
+                    if row.columns.binary_search(&c).is_ok() {
+                        reduction.weight[c as usize] -= 1;
+                    } else {
+                        reduction.weight[c as usize] += 1;
+                        reduction.rows_of[c as usize].push(r);
+                    }
+                    reduction.queue(c as usize);
+                }
+                row.columns = symmetric_difference(&row.columns, &pivot_row.columns);
+                row.rows = symmetric_difference(&row.rows, &pivot_row.rows);
+            }
+        }
+
+        // more rows than needed only make the dense part larger, the heaviest go first
+        let live_columns = reduction.weight.iter().filter(|&&w| w > 0).count();
+        let mut live: Vec<u32> = (0..sparse.len() as u32)
+            .filter(|&r| sparse[r as usize].is_some())
+            .collect();
+        if live.len() <= live_columns + SURPLUS_ROWS {
+            break;
+        }
+        live.sort_by_key(|&r| {
+            sparse[r as usize]
+                .as_ref()
+                .map_or(0, |row| row.columns.len())
+        });
+        for &r in &live[live_columns + SURPLUS_ROWS..] {
+            reduction.drop_row(&mut sparse, r);
+        }
+    }
+    sparse.into_iter().flatten().collect()
+}
+
+// the column counts of the structured elimination
+struct Reduction {
+    weight: Vec<usize>,
+    // the rows of every column, among them stale ones that lost it since
+    rows_of: Vec<Vec<u32>>,
+    // the columns of up to MERGE_WEIGHT rows with their weight when they were queued
+    light: BinaryHeap<Reverse<(usize, usize)>>,
+}
+
+impl Reduction {
+    fn queue(&mut self, c: usize) {
+        if (1..=MERGE_WEIGHT).contains(&self.weight[c]) {
+            self.light.push(Reverse((self.weight[c], c)));
+        }
+    }
+
+    // the live rows of column c, without the stale ones
+    fn rows(&mut self, sparse: &[Option<Sparse>], c: usize) -> Vec<u32> {
+        let rows = &mut self.rows_of[c];
+        rows.retain(|&r| {
+            sparse[r as usize]
+                .as_ref()
+                .is_some_and(|row| row.columns.binary_search(&(c as u32)).is_ok())
+        });
+        rows.sort_unstable();
+        rows.dedup();
+        rows.clone()
+    }
+
+    fn drop_row(&mut self, sparse: &mut [Option<Sparse>], r: u32) -> Sparse {
+        let row = sparse[r as usize]
+            .take()
+            .expect("only live rows are dropped");
+        for &c in &row.columns {
+            self.weight[c as usize] -= 1;
+            self.queue(c as usize);
+        }
+        row
+    }
+}
+
+// dense elimination, each row tracks the rows it is the sum of. the dependencies as
+// indices into the rows
+fn dense_dependencies(rows: &[Sparse], columns: usize) -> Vec<Vec<usize>> {
+    let mut index = vec![usize::MAX; columns];
+    let mut width = 0;
+    for &c in rows.iter().flat_map(|row| &row.columns) {
+        if index[c as usize] == usize::MAX {
+            index[c as usize] = width;
+            width += 1;
+        }
+    }
+    let history = width.div_ceil(64);
+    let words = history + rows.len().div_ceil(64);
+    let mut matrix: Vec<Vec<u64>> = rows
+        .iter()
+        .enumerate()
+        .map(|(k, row)| {
+            let mut bits = vec![0u64; words];
+            for &c in &row.columns {
+                let c = index[c as usize];
+                bits[c / 64] |= 1 << (c % 64);
+            }
+            bits[history + k / 64] |= 1 << (k % 64);
+            bits
+        })
+        .collect();
+
+    // the method of four russians: the pivots of BLOCK columns at a time go into a table
+    // of all their sums, and every other row adds the one sum that clears the block
+    let mut pivoted = vec![false; rows.len()];
+    for first in (0..width).step_by(BLOCK) {
+        let word = first / 64;
+        let window = |row: &[u64]| (row[word] >> (first % 64)) as usize & ((1 << BLOCK) - 1);
+        // the pivots of the block, each zero in the columns of the ones before, and
+        // which of them a row with these bits in the block needs
+        let mut pivots: Vec<usize> = Vec::with_capacity(BLOCK);
+        let mut bits: Vec<usize> = Vec::with_capacity(BLOCK);
+        let needed = |pivot_bits: &[usize], mut v: usize| {
+            let mut mask = 0;
+            for (i, &b) in pivot_bits.iter().enumerate() {
+                if v & b & b.wrapping_neg() != 0 {
+                    v ^= b;
+                    mask |= 1 << i;
+                }
+            }
+            mask
+        };
+        for c in first..(first + BLOCK).min(width) {
+            let bit = 1 << (c - first);
+            let Some(pivot) = (0..rows.len()).find(|&r| {
+                if pivoted[r] {
+                    return false;
+                }
+                let v = window(&matrix[r]);
+                let mask = needed(&bits, v);
+                let reduced = (0..pivots.len())
+                    .filter(|i| mask >> i & 1 == 1)
+                    .fold(v, |v, i| v ^ bits[i]);
+                reduced & bit != 0
+            }) else {
+                continue;
+            };
+            let mask = needed(&bits, window(&matrix[pivot]));
+            for (i, &p) in pivots.iter().enumerate() {
+                if mask >> i & 1 == 1 {
+                    let (row, other) = pair_mut(&mut matrix, pivot, p);
+                    for (a, b) in row[word..].iter_mut().zip(&other[word..]) {
+                        *a ^= b;
+                    }
+                }
+            }
+            pivoted[pivot] = true;
+            bits.push(window(&matrix[pivot]));
+            pivots.push(pivot);
+        }
+        if pivots.is_empty() {
+            continue;
+        }
+
+        // table[mask] is the sum of the pivots in mask, one addition each
+        let mut table = vec![vec![0u64; words - word]; 1 << pivots.len()];
+        for mask in 1..table.len() {
+            let lowest = mask.trailing_zeros() as usize;
+            let (sum, rest) = pair_mut(&mut table, mask, mask & (mask - 1));
+            for ((a, b), c) in sum
+                .iter_mut()
+                .zip(rest.iter())
+                .zip(&matrix[pivots[lowest]][word..])
+            {
+                *a = b ^ c;
+            }
+        }
+        // the rows without a pivot are zero before this block, the pivots are left as
+        // they are
+        matrix
+            .par_iter_mut()
+            .zip(&pivoted)
+            .for_each(|(row, &done)| {
+                let mask = needed(&bits, window(row));
+                if !done && mask != 0 {
+                    for (a, b) in row[word..].iter_mut().zip(&table[mask]) {
+                        *a ^= b;
+                    }
+                }
+            });
+    }
+
+    // the rows left without a pivot are zero, their history is a dependency
+    (0..rows.len())
+        .filter(|&r| !pivoted[r])
+        .map(|r| {
+            (0..rows.len())
+                .filter(|&k| matrix[r][history + k / 64] >> (k % 64) & 1 == 1)
+                .collect()
+        })
+        .collect()
+}
+
+// mutable a and shared b of two different elements
+fn pair_mut<T>(items: &mut [T], a: usize, b: usize) -> (&mut T, &T) {
+    if a < b {
+        let (low, high) = items.split_at_mut(b);
+        (&mut low[a], &high[0])
+    } else {
+        let (low, high) = items.split_at_mut(a);
+        (&mut high[0], &low[b])
+    }
+}
+
+// xorshift64, random but the same on every run
+struct XorShift(u64);
+
+impl XorShift {
+    fn below(&mut self, bound: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % bound as u64) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn square_roots_mod_p() {
+        for p in [3, 5, 13, 17, 97, 65_537, 1_000_000_007] {
+            for a in 1..200 {
+                let square = mul_mod(a, a, p);
+                let r = sqrt_mod(square, p);
+                assert_eq!(square, mul_mod(r, r, p), "{a}^2 mod {p}");
+            }
+        }
+    }
+
+    #[test]
+    fn even_dependencies() {
+        // columns 0..4, rows 0 + 1 and 2 + 3 + 4 are the dependencies, row 5 has the
+        // only entry of column 4
+        let rows: [&[u32]; 6] = [
+            &[0, 1],
+            &[1, 0, 2, 2],
+            &[1, 2],
+            &[2, 3, 3, 3],
+            &[1, 3],
+            &[4, 0],
+        ];
+        let mut found = dependencies(&rows, 5);
+        for subset in &mut found {
+            subset.sort_unstable();
+        }
+        found.sort();
+        assert_eq!(vec![vec![0, 1], vec![2, 3, 4]], found);
+    }
+
+    #[test]
+    fn dependencies_of_a_large_matrix() {
+        // the factor base of 90 digits. the relations of a real run have about 24 odd
+        // primes each, and column i turns up in about 1 / i of them
+        let columns = 24_000;
+        let mut rng = XorShift(0x9e37_79b9_7f4a_7c15);
+        let rows: Vec<Vec<u32>> = (0..columns + EXTRA_RELATIONS)
+            .map(|_| {
+                (0..24)
+                    .map(|_| {
+                        let u = rng.below(1 << 30) as f64 / (1 << 30) as f64;
+                        ((columns as f64 + 1.0).powf(u) - 1.0) as u32
+                    })
+                    .collect()
+            })
+            .collect();
+        let refs: Vec<&[u32]> = rows.iter().map(|row| &row[..]).collect();
+        let found = dependencies(&refs, columns);
+        assert!(found.len() >= EXTRA_RELATIONS, "{}", found.len());
+        for subset in &found {
+            let mut counts = vec![0u32; columns];
+            for &r in subset {
+                for &c in &rows[r] {
+                    counts[c as usize] += 1;
+                }
+            }
+            assert!(counts.iter().all(|count| count % 2 == 0));
+        }
+    }
+
+    #[test]
+    fn splits_semiprimes() {
+        let cases = [
+            (100_000_000_000_031u128, 300_000_000_000_089u128),
+            (10_000_000_000_000_000_051, 20_000_000_000_000_000_011),
+        ];
+        for (p, q) in cases {
+            let d = siqs(&BigUint::from(p * q)).unwrap();
+            assert!(d == BigUint::from(p) || d == BigUint::from(q), "{p} * {q}");
+        }
+    }
+
+    #[test]
+    fn degenerate_inputs() {
+        let p = BigUint::from(1_000_000_007u64);
+        assert_eq!(Some(p.clone()), siqs(&(&p * &p)));
+        // a factor below the largest prime of the base turns up while building it
+        let n = &p * &BigUint::from(101u64);
+        assert_eq!(Some(BigUint::from(101u64)), siqs(&n));
+        assert_eq!(None, siqs(&BigUint::one()));
+        assert_eq!(None, siqs(&p));
+    }
+}
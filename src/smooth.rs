@@ -0,0 +1,224 @@
+//! Pollard's p − 1 and Williams' p + 1.
+//!
+//! Both find a prime factor `p` of `n` when `p − 1`, or `p + 1`, is a product of small
+//! prime powers, no matter how large `p` itself is. Pollard's rho needs about `√p`
+//! steps for the same factor.
+
+use crate::{collect_primes, ring::Residues};
+
+// prime powers between two gcds in stage 1, a gcd costs a few hundred multiplications
+const BATCH: usize = 64;
+
+// primes between two gcds in stage 2
+const STAGE_TWO_BATCH: usize = 1024;
+
+// p + 1 only works if the discriminant seed^2 - 4 is a non-residue mod p, these three
+// give 5, 12 and 32, from the classes of 5, 3 and 2
+const SEEDS: [i64; 3] = [3, 4, 6];
+
+// p - 1 with a^M for M the product of the prime powers up to b1, then one more prime
+// in (b1, b2], returns a divisor of n that is neither 1 nor n
+pub(crate) fn p_minus_one<R: Residues>(ring: &R, b1: u64, b2: u64) -> Option<R::T> {
+    let one = ring.residue(1);
+    let zero = ring.residue(0);
+
+    let mut x = ring.residue(3);
+    for batch in collect_primes(0, b1).chunks(BATCH) {
+        let checkpoint = x.clone();
+        for &p in batch {
+            x = ring.pow(&x, prime_power(p, b1));
+        }
+        let x1 = ring.sub(&x, &one);
+        if x1 == zero {
+            // every factor of n turned up in this batch, go through it again one prime at
+            // a time and hope they turn up separately
+            return separate(ring, checkpoint, batch, b1);
+        }
+        if let Some(d) = ring.divisor(&x1) {
+            return Some(d);
+        }
+    }
+
+    stage_two(ring, &x, b1.max(2) + 1, b2)
+}
+
+fn separate<R: Residues>(ring: &R, mut x: R::T, batch: &[u64], b1: u64) -> Option<R::T> {
+    let one = ring.residue(1);
+    for &p in batch {
+        let mut power = p;
+        loop {
+            x = ring.pow(&x, p);
+            let x1 = ring.sub(&x, &one);
+            if let Some(d) = ring.divisor(&x1) {
+                return Some(d);
+            }
+            if x1 == ring.residue(0) {
+                return None;
+            }
+            if power > b1 / p {
+                break;
+            }
+            power *= p;
+        }
+    }
+    None
+}
+
+// product of x^q - 1 over the primes q in [start, b2], x^q follows from the previous
+// prime by multiplying with x^gap for the even gap between them
+fn stage_two<R: Residues>(ring: &R, x: &R::T, start: u64, b2: u64) -> Option<R::T> {
+    let primes = collect_primes(start, b2);
+    let &first = primes.first()?;
+    let one = ring.residue(1);
+    let zero = ring.residue(0);
+
+    // steps[i] = x^(2i + 2), filled in as larger gaps turn up
+    let x2 = ring.mul(x, x);
+    let mut steps = vec![x2.clone()];
+    let mut y = ring.pow(x, first);
+    let mut acc = ring.sub(&y, &one);
+    for (i, pair) in primes.windows(2).enumerate() {
+        let gap = ((pair[1] - pair[0]) / 2) as usize;
+        while steps.len() < gap {
+            let next = ring.mul(&steps[steps.len() - 1], &x2);
+            steps.push(next);
+        }
+        y = ring.mul(&y, &steps[gap - 1]);
+        acc = ring.mul(&acc, &ring.sub(&y, &one));
+
+        if i % STAGE_TWO_BATCH == STAGE_TWO_BATCH - 1 || i + 2 == primes.len() {
+            if acc == zero {
+                return None;
+            }
+            if let Some(d) = ring.divisor(&acc) {
+                return Some(d);
+            }
+        }
+    }
+    ring.divisor(&acc)
+}
+
+// p + 1 with the lucas sequence V_M(seed) for M the product of the prime powers up to
+// b1, tried with each seed until one gives a divisor of n that is neither 1 nor n
+pub(crate) fn p_plus_one<R: Residues>(ring: &R, b1: u64) -> Option<R::T> {
+    let primes = collect_primes(0, b1);
+    let two = ring.residue(2);
+    let zero = ring.residue(0);
+
+    for seed in SEEDS {
+        let mut v = ring.residue(seed);
+        for batch in primes.chunks(BATCH) {
+            for &p in batch {
+                v = lucas(ring, &v, prime_power(p, b1));
+            }
+            let v2 = ring.sub(&v, &two);
+            if v2 == zero {
+                break;
+            }
+            if let Some(d) = ring.divisor(&v2) {
+                return Some(d);
+            }
+        }
+    }
+    None
+}
+
+// V_k of the lucas sequence with P = v and Q = 1, by a ladder on (V_j, V_j+1)
+fn lucas<R: Residues>(ring: &R, v: &R::T, k: u64) -> R::T {
+    let two = ring.residue(2);
+    let (mut x, mut y) = (v.clone(), ring.sub(&ring.mul(v, v), &two));
+    for i in (0..63 - k.leading_zeros()).rev() {
+        // V_2j = V_j^2 - 2, V_2j+1 = V_j V_j+1 - V_1
+        let xy = ring.sub(&ring.mul(&x, &y), v);
+        if k >> i & 1 == 1 {
+            x = xy;
+            y = ring.sub(&ring.mul(&y, &y), &two);
+        } else {
+            y = xy;
+            x = ring.sub(&ring.mul(&x, &x), &two);
+        }
+    }
+    x
+}
+
+// largest power of p that is at most bound
+pub(crate) fn prime_power(p: u64, bound: u64) -> u64 {
+    let mut power = p;
+    while power <= bound / p {
+        power *= p;
+    }
+    power
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        bigint::BigUint,
+        montgomery::{BigMontgomery, Montgomery},
+    };
+
+    #[test]
+    fn lucas_sequence() {
+        // V_k(3) with Q = 1: 2, 3, 7, 18, 47, 123, 322, 843
+        let ring = Montgomery::new(1_000_003);
+        let v = ring.residue(3);
+        for (k, expected) in [
+            (1, 3),
+            (2, 7),
+            (3, 18),
+            (4, 47),
+            (5, 123),
+            (6, 322),
+            (7, 843),
+        ] {
+            assert_eq!(ring.residue(expected), lucas(&ring, &v, k), "V_{k}");
+        }
+        // V_mk = V_m(V_k)
+        let v6 = lucas(&ring, &v, 6);
+        assert_eq!(lucas(&ring, &v, 42), lucas(&ring, &v6, 7));
+    }
+
+    #[test]
+    fn smooth_p_minus_one() {
+        // 1_000_001_269 - 1 = 2^2 * 3^3 * 7 * 17^2 * 23 * 199, 1_000_000_007 - 1 = 2 * 500_000_003
+        let n: u128 = 1_000_001_269 * 1_000_000_007;
+        assert_eq!(
+            Some(1_000_001_269),
+            p_minus_one(&Montgomery::new(n), 1000, 1000)
+        );
+        // 1_000_000_033 - 1 = 2^5 * 3 * 127 * 82_021 needs stage 2
+        let ring = Montgomery::new(1_000_000_033 * 1_000_000_007);
+        assert_eq!(None, p_minus_one(&ring, 1000, 50_000));
+        assert_eq!(Some(1_000_000_033), p_minus_one(&ring, 1000, 100_000));
+
+        // 1_000_001_969 - 1 = 2^4 * 7 * 31 * 293 * 983, both factors turn up in the
+        // first batch and are separated one prime at a time
+        let n: u128 = 1_000_001_269 * 1_000_001_969;
+        let d = p_minus_one(&Montgomery::new(n), 1000, 1000).unwrap();
+        assert!(d == 1_000_001_269 || d == 1_000_001_969);
+
+        let p = &(&BigUint::one() << 127) - &BigUint::one();
+        let n = &p * &BigUint::from(1_000_001_269u64);
+        let ring = BigMontgomery::new(&n);
+        assert_eq!(
+            Some(BigUint::from(1_000_001_269u64)),
+            p_minus_one(&ring, 1000, 1000)
+        );
+    }
+
+    #[test]
+    fn smooth_p_plus_one() {
+        // 1_000_004_633 + 1 = 2 * 3^2 * 17 * 31 * 271 * 389, 1_000_000_007 + 1 has 109^2
+        let ring = Montgomery::new(1_000_004_633 * 1_000_000_007);
+        assert_eq!(Some(1_000_004_633), p_plus_one(&ring, 1000));
+
+        let p = &(&BigUint::one() << 127) - &BigUint::one();
+        let n = &p * &BigUint::from(1_000_004_633u64);
+        let ring = BigMontgomery::new(&n);
+        assert_eq!(
+            Some(BigUint::from(1_000_004_633u64)),
+            p_plus_one(&ring, 1000)
+        );
+    }
+}
@@ -0,0 +1,285 @@
+use prime_factorization::{
+    Factor, Prime, ProbablePrime,
+    arith::{divisor_count, divisor_sum, euler_phi, gcd, mobius, mul_mod, pow_mod},
+    audit::{Weakness, audit},
+    batch::{batch_gcd, product_tree, shared_factors},
+    bigint::BigUint,
+    collect_primes, collect_primes_u128, factor,
+    factor::{Method, Pipeline, Stage, divisor_by, find_divisor},
+    fermat::fermat,
+    hart::hart,
+    roots::{exact_sqrt, icbrt, iroot, isqrt},
+    semiprime::{
+        almost_primes, factorize, pair_count, par_for_each_semiprime, par_sorted_semiprimes,
+        semiprime_count, semiprimes, sorted_semiprimes,
+    },
+    sieve::segmented_sieve,
+    siqs::siqs,
+    squfof::squfof,
+};
+
+#[test]
+fn primality() {
+    assert!(2u64.prime());
+    assert!(!1u64.prime());
+    assert!((u64::MAX - 58).prime());
+    assert!(!(u64::MAX).prime());
+}
+
+#[test]
+fn prime_ranges() {
+    assert_eq!(vec![2, 3, 5, 7], collect_primes(0, 10));
+    assert_eq!(
+        collect_primes(1_000_000, 1_010_000),
+        segmented_sieve(1_000_000, 1_010_000)
+    );
+    assert!(collect_primes(10, 0).is_empty());
+    assert!(
+        collect_primes(u64::MAX - 100, u64::MAX)
+            .iter()
+            .all(|p| p.prime())
+    );
+}
+
+#[test]
+fn factorization() {
+    assert_eq!(vec![(2, 3), (3, 2), (5, 1)], factor(360));
+    assert!(factor(1).is_empty());
+
+    let n = 4_294_967_291 * 4_294_967_279;
+    assert_eq!(vec![(4_294_967_279, 1), (4_294_967_291, 1)], factor(n));
+}
+
+#[test]
+fn semiprime_enumeration() {
+    let primes = collect_primes(0, 100);
+    let all = factorize(primes.clone());
+
+    assert_eq!(pair_count(&primes), all.len() as u128);
+    assert_eq!(all.len(), semiprimes(&primes).count());
+    assert_eq!(all, sorted_semiprimes(&primes).collect());
+    assert!(par_sorted_semiprimes(&primes).eq(sorted_semiprimes(&primes)));
+
+    let sorted: Vec<u128> = sorted_semiprimes(&primes).map(|(n, _, _)| n).collect();
+    assert!(sorted.windows(2).all(|w| w[0] < w[1]));
+
+    let counted: usize = par_for_each_semiprime(&primes, || 0, |count, _| *count += 1)
+        .into_iter()
+        .sum();
+    assert_eq!(all.len(), counted);
+
+    assert_eq!(
+        all.iter().filter(|&&(n, _, _)| n <= 1000).count() as u128,
+        semiprime_count(&primes, 1000)
+    );
+    // 4, 6 and 9
+    assert_eq!(
+        3,
+        almost_primes(&[2, 3, 5], 2, true)
+            .iter()
+            .filter(|(n, _)| *n < 10)
+            .count()
+    );
+}
+
+#[test]
+fn arithmetic_functions() {
+    assert_eq!(6, gcd(12, 18));
+    assert_eq!(1, mul_mod(u64::MAX, u64::MAX, 7));
+    assert_eq!(1024 % 1000, pow_mod(2, 10, 1000));
+    assert_eq!(96, euler_phi(360));
+    assert_eq!(24, divisor_count(360));
+    assert_eq!(1170, divisor_sum(360));
+    assert_eq!(0, mobius(360));
+    assert_eq!(-1, mobius(30));
+}
+
+#[test]
+fn integer_roots() {
+    let p = 4_294_967_291u64;
+    assert_eq!(p - 1, isqrt(p * p - 1));
+    assert_eq!(p, isqrt(p * p));
+    assert_eq!(Some(p), exact_sqrt(p * p));
+    assert_eq!(2_642_245, icbrt(u64::MAX));
+    assert_eq!(1_625, iroot(u64::MAX, 6));
+}
+
+#[test]
+fn wide_integers() {
+    assert!(65_521u32.prime());
+    assert!(((1u128 << 127) - 1).prime());
+    assert_eq!(vec![(65_521, 2)], (65_521u32 * 65_521).factor());
+
+    // 31 digits
+    let n: u128 = 1_000_000_007 * 10_000_000_019 * 1_000_000_000_039;
+    assert_eq!(
+        vec![
+            (1_000_000_007, 1),
+            (10_000_000_019, 1),
+            (1_000_000_000_039, 1)
+        ],
+        n.factor()
+    );
+    assert_eq!(
+        vec![(1 << 64) + 13],
+        collect_primes_u128(1 << 64, (1 << 64) + 20)
+    );
+}
+
+#[test]
+fn big_integers() {
+    let m = &(&BigUint::one() << 521) - &BigUint::one();
+    assert!((&m).prime());
+    let n = &m * &BigUint::from(1_000_000_007u64);
+    assert_eq!(
+        vec![(BigUint::from(1_000_000_007u64), 1), (m.clone(), 1)],
+        n.factor()
+    );
+    assert_eq!(
+        "6864797660130609714981900799081393217269435300143305409394463459185543183397656052122559640661454554977296311391480858037121987999716643812574028291115057151",
+        m.to_string()
+    );
+}
+
+#[test]
+fn probable_primes() {
+    for n in [2047u64, 561, 3_215_031_751] {
+        assert!(!n.probable_prime());
+    }
+    assert!(((1u128 << 127) - 1).probable_prime());
+    let m = &(&BigUint::one() << 607) - &BigUint::one();
+    assert!((&m).probable_prime());
+    assert!(!(&m * &m).probable_prime());
+}
+
+#[test]
+fn factoring_stages() {
+    // 1_000_001_269 - 1 and 1_000_004_633 + 1 only have small prime factors
+    let n: u128 = 1_000_001_269 * 1_000_004_633 * (1 << 50);
+    assert_eq!(
+        vec![
+            (2, 50, Method::TrialDivision),
+            (1_000_001_269, 1, Method::PMinusOne),
+            (1_000_004_633, 1, Method::PMinusOne),
+        ],
+        n.factor_with(&Pipeline::default())
+    );
+    let pp1 = Pipeline::new().stage(Stage::PPlusOne { b1: 1000 });
+    assert_eq!(Method::PPlusOne, n.factor_with(&pp1)[2].2);
+}
+
+#[test]
+fn elliptic_curves() {
+    let (p, q) = (1_000_000_000_000_091u128, 3_000_000_000_000_301u128);
+    let pipeline = Pipeline::new().stage(Stage::ecm(15));
+    assert_eq!(
+        vec![(p, 1, Method::Ecm), (q, 1, Method::Ecm)],
+        (p * q).factor_with(&pipeline)
+    );
+    assert_eq!(vec![(p, 1), (q, 1)], (p * q).factor());
+}
+
+#[test]
+fn quadratic_sieve() {
+    let (p, q) = (
+        10_000_000_000_000_000_051u128,
+        20_000_000_000_000_000_011u128,
+    );
+    let pipeline = Pipeline::new().stage(Stage::Siqs);
+    assert_eq!(
+        vec![(p, 1, Method::Siqs), (q, 1, Method::Siqs)],
+        (p * q).factor_with(&pipeline)
+    );
+    assert_eq!(Some(BigUint::from(p)), siqs(&BigUint::from(p * q)));
+    assert_eq!(None, siqs(&BigUint::from(1_000_000_007u64)));
+}
+
+#[test]
+fn mid_size_methods() {
+    // from factorize, as the benchmarks take them
+    for (n, p, q) in factorize(vec![1_000_003, 1_000_033, 1_000_000_007, 4_000_000_007]) {
+        let n = n as u64;
+        let proper = |d: u64| d == p || d == q;
+        assert!(find_divisor(n).is_some_and(|(d, _)| proper(d)));
+        assert!(divisor_by(n, Method::Rho).is_some_and(proper));
+        assert!(hart(n).is_none_or(proper));
+        assert!(squfof(n).is_none_or(proper));
+    }
+    assert_eq!(Some((3, Method::TrialDivision)), find_divisor(3 * 65_537));
+    assert_eq!(
+        Some(1_000_003),
+        divisor_by(1_000_003 * 1_000_000_007, Method::TrialDivision)
+    );
+    assert_eq!(
+        Some((1_000_003, Method::Hart)),
+        find_divisor(1_000_003 * 1_000_033)
+    );
+    assert_eq!(
+        vec![
+            (1_000_003, 1, Method::Rho),
+            (1_000_000_000_039, 1, Method::Rho)
+        ],
+        (1_000_003u64 * 1_000_000_000_039).factor_with(&Pipeline::new())
+    );
+    assert_eq!(None, find_divisor(1_000_000_007));
+    assert_eq!(None, divisor_by(1_000_003 * 1_000_033, Method::Siqs));
+}
+
+#[test]
+fn weak_moduli() {
+    let (p, q) = (
+        18_446_744_073_709_551_557u128,
+        18_446_744_073_709_551_629u128,
+    );
+    let pipeline = Pipeline::new().stage(Stage::Fermat { steps: 1 });
+    assert_eq!(
+        vec![(p, 1, Method::Fermat), (q, 1, Method::Fermat)],
+        (p * q).factor_with(&pipeline)
+    );
+    assert_eq!(Some(BigUint::from(p)), fermat(&BigUint::from(p * q), 1));
+
+    // the triples of factorize are moduli with known factors
+    for (n, p, q) in factorize(vec![65_521, 1_000_001_269, 4_000_000_559]) {
+        let finding = audit(&BigUint::from(n)).unwrap();
+        assert_eq!(
+            (BigUint::from(p), BigUint::from(q)),
+            (finding.p.clone(), finding.q.clone())
+        );
+        // 1_000_001_269 - 1 is smooth, 4_000_000_559 - 1 = 2 * 2_000_000_279 is not
+        let weakness = match p {
+            65_521 => Weakness::SmallFactor,
+            _ => Weakness::SmoothPMinusOne,
+        };
+        assert_eq!(weakness, finding.weakness);
+    }
+    assert_eq!(
+        None,
+        audit(&BigUint::from(1_000_000_007u64 * 4_000_000_559))
+    );
+}
+
+#[test]
+fn batch_gcds() {
+    // all pairs of five primes, every one shares a prime with another
+    let triples: Vec<(u128, u64, u64)> =
+        factorize(vec![1_000_003, 1_000_033, 1_000_037, 1_000_039, 1_000_081])
+            .into_iter()
+            .collect();
+    let moduli: Vec<BigUint> = triples.iter().map(|&(n, _, _)| BigUint::from(n)).collect();
+    let shared = shared_factors(&moduli);
+    assert_eq!(moduli.len(), shared.len());
+    for (i, p, q) in shared {
+        let (_, tp, tq) = triples[i];
+        assert_eq!((BigUint::from(tp), BigUint::from(tq)), (p, q));
+    }
+
+    let moduli = [15u64, 77, 143].map(BigUint::from);
+    assert_eq!(
+        vec![BigUint::one(), BigUint::from(11u64), BigUint::from(11u64)],
+        batch_gcd(&moduli)
+    );
+    assert_eq!(
+        Some(&vec![BigUint::from(15u64 * 77 * 143)]),
+        product_tree(&moduli).last()
+    );
+}